@@ -5,14 +5,17 @@ use street_sim_rs::*;
 fn bench_street_matcher(c: &mut Criterion) {
     c.bench_function("Place constructor", |b| {
         b.iter(|| {
-            Place::new(black_box("Bern City"));
+            Place::new(black_box("Bern City"), &DataPaths::default()).unwrap();
         })
     });
 
     c.bench_function("StreetMatcher new approach", |b| {
         b.iter(|| {
             street_sim::find_matches::<Plz>(
-                black_box(&Street::new("Mühlematt 7-11 (Emmenbrücke)", None).unwrap()),
+                black_box(
+                    &Street::new("Mühlematt 7-11 (Emmenbrücke)", None, &DataPaths::default())
+                        .unwrap(),
+                ),
                 black_box(StreetConfig::default()),
             )
             .unwrap();
@@ -21,12 +24,16 @@ fn bench_street_matcher(c: &mut Criterion) {
 
     c.bench_function("TextMatcher cfind", |b| {
         b.iter(|| {
-            let cfg = Config::new(Sens::new(0.6), 100, SimAlgo::JaroWinkler, None);
+            let cfg = Config::new(Sens::new(0.6).unwrap(), 100, SimAlgo::JaroWinkler, None);
             text_sim::fast_cmp_with_file(
                 black_box(
-                    &Street::new("ch de saint-cierges 3,fas23dfsfsdf", None)
-                        .unwrap()
-                        .value,
+                    &Street::new(
+                        "ch de saint-cierges 3,fas23dfsfsdf",
+                        None,
+                        &DataPaths::default(),
+                    )
+                    .unwrap()
+                    .value,
                 ),
                 black_box(&PathBuf::from("./test_data/street_names.txt")),
                 black_box(&cfg),
@@ -36,7 +43,7 @@ fn bench_street_matcher(c: &mut Criterion) {
     });
     c.bench_function("TextMatcher find", |b| {
         b.iter(|| {
-            let cfg = Config::new(Sens::new(0.5), 500, SimAlgo::Jaro, None);
+            let cfg = Config::new(Sens::new(0.5).unwrap(), 500, SimAlgo::Jaro, None);
             text_sim::cmp_with_file(
                 black_box(&Text::new("ch de saint-cierges 3".to_owned())),
                 black_box(&PathBuf::from("./test_data/street_names.txt")),
@@ -47,15 +54,19 @@ fn bench_street_matcher(c: &mut Criterion) {
     c.bench_function("StreetMatcher by place with dir", |b| {
         b.iter(|| {
             street_sim::find_matches(
-                black_box(&Street::new("ch de saint-cierges 3", None).unwrap()),
-                black_box(StreetConfig::default_with(Place::new("bercher"))),
+                black_box(
+                    &Street::new("ch de saint-cierges 3", None, &DataPaths::default()).unwrap(),
+                ),
+                black_box(StreetConfig::default_with(
+                    Place::new("bercher", &DataPaths::default()).unwrap(),
+                )),
             )
         })
     });
     c.bench_function("StreetMatcher with dir", |b| {
         b.iter(|| {
             street_sim::find_matches(
-                black_box(&Street::new("qu du seujet 36", None).unwrap()),
+                black_box(&Street::new("qu du seujet 36", None, &DataPaths::default()).unwrap()),
                 black_box(StreetConfig::default_with(Plz::new(1201))),
             )
         })
@@ -63,7 +74,7 @@ fn bench_street_matcher(c: &mut Criterion) {
     c.bench_function("StreetMatcher without dir ", |b| {
         b.iter(|| {
             street_sim::find_matches::<Plz>(
-                black_box(&Street::new("qu du seujet 36", None).unwrap()),
+                black_box(&Street::new("qu du seujet 36", None, &DataPaths::default()).unwrap()),
                 black_box(StreetConfig::default()),
             )
         })
@@ -71,7 +82,7 @@ fn bench_street_matcher(c: &mut Criterion) {
     c.bench_function("StreetMatcher without dir missed first letter", |b| {
         b.iter(|| {
             street_sim::find_matches::<Plz>(
-                black_box(&Street::new("uai du seujet 36", None).unwrap()),
+                black_box(&Street::new("uai du seujet 36", None, &DataPaths::default()).unwrap()),
                 black_box(StreetConfig::default()),
             )
         })
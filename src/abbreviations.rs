@@ -0,0 +1,350 @@
+//! This module expands abbreviations of street names in German, French, Italian and Romansh.
+//! The dictionaries are loaded from a TOML file, see `data/abbreviations.toml` for the format
+use crate::mass::Error;
+use std::{collections::HashMap, fs, path::Path};
+use toml::Value;
+
+const DEFAULT_ABBREVIATIONS: &str = include_str!("../data/abbreviations.toml");
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Abbreviations {
+    words: HashMap<String, String>,
+    suffixes: Vec<(String, String)>,
+    /// The abbreviations matched against the start of a hyphenated token: 'st-' in 'st-légier'
+    prefixes: Vec<(String, String)>,
+    /// The abbreviations meaning different words in several languages ('pl.' is 'platz'
+    /// or 'place') with the full word of each language
+    ambiguous: HashMap<String, Vec<(String, String)>>,
+    /// The words telling the language of a text ('de', 'la' for French) and the endings
+    /// of the compound words of the language ('strasse'), in the order of languages
+    markers: Vec<(String, Vec<String>, Vec<String>)>,
+}
+
+impl Abbreviations {
+    /// The dictionaries shipped with the crate
+    pub fn builtin() -> Self {
+        Self::from_toml(DEFAULT_ABBREVIATIONS).expect("valid builtin abbreviations")
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read or parsed
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns `Error::Toml` if `toml` is not valid and `Error::InvalidConfig`
+    /// if it does not follow the format of `data/abbreviations.toml`
+    pub fn from_toml(toml: &str) -> Result<Self, Error> {
+        let values = toml::from_str::<Value>(toml)?;
+        let table = values
+            .as_table()
+            .ok_or_else(|| Error::InvalidConfig("abbreviations must be a table".to_string()))?;
+        let order = match table.get("order") {
+            Some(order) => order
+                .as_array()
+                .and_then(|order| {
+                    order
+                        .iter()
+                        .map(Value::as_str)
+                        .collect::<Option<Vec<&str>>>()
+                })
+                .ok_or_else(|| {
+                    Error::InvalidConfig("'order' must be an array of strings".to_string())
+                })?,
+            None => {
+                let mut languages = table.keys().map(String::as_str).collect::<Vec<&str>>();
+                languages.sort_unstable();
+                languages
+            }
+        };
+        let mut abbreviations = Self::default();
+        let mut meanings = HashMap::<String, Vec<(String, String)>>::new();
+        for name in order {
+            let language = table
+                .get(name)
+                .ok_or_else(|| Error::InvalidConfig(format!("unknown language '{}'", name)))?;
+            for (abbr, full) in Self::read_pairs(language, "words")? {
+                meanings
+                    .entry(abbr)
+                    .or_default()
+                    .push((name.to_string(), full));
+            }
+            abbreviations.extend(&[], &Self::read_pairs(language, "suffixes")?);
+            abbreviations
+                .prefixes
+                .extend(Self::read_pairs(language, "prefixes")?);
+            abbreviations.markers.push((
+                name.to_string(),
+                Self::read_markers(language, "markers")?,
+                Self::read_markers(language, "endings")?,
+            ));
+        }
+        for (abbr, languages) in meanings {
+            if languages.iter().all(|(_, full)| *full == languages[0].1) {
+                abbreviations.words.insert(abbr, languages[0].1.to_owned());
+            } else {
+                abbreviations.ambiguous.insert(abbr, languages);
+            }
+        }
+        // The longest prefix should be tried first: 'ste-' before 'st-'
+        abbreviations
+            .prefixes
+            .sort_by(|(lhs, _), (rhs, _)| rhs.len().cmp(&lhs.len()).then(lhs.cmp(rhs)));
+        Ok(abbreviations)
+    }
+
+    #[inline]
+    fn read_markers(language: &Value, key: &str) -> Result<Vec<String>, Error> {
+        match language.get(key) {
+            Some(markers) => markers
+                .as_array()
+                .and_then(|markers| {
+                    markers
+                        .iter()
+                        .map(|marker| Some(marker.as_str()?.to_lowercase()))
+                        .collect()
+                })
+                .ok_or_else(|| {
+                    Error::InvalidConfig(format!("'{}' must be an array of strings", key))
+                }),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    #[inline]
+    fn read_pairs(language: &Value, key: &str) -> Result<Vec<(String, String)>, Error> {
+        match language.get(key) {
+            Some(pairs) => pairs
+                .as_table()
+                .and_then(|pairs| {
+                    pairs
+                        .iter()
+                        .map(|(abbr, full)| {
+                            Some((abbr.to_lowercase(), full.as_str()?.to_lowercase()))
+                        })
+                        .collect()
+                })
+                .ok_or_else(|| {
+                    Error::InvalidConfig(format!("'{}' must be a table of strings", key))
+                }),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Adds the abbreviations to the dictionary, the already known ones are not overwritten
+    pub fn extend(&mut self, words: &[(String, String)], suffixes: &[(String, String)]) {
+        for (abbr, full) in words {
+            let abbr = abbr.to_lowercase();
+            if !self.ambiguous.contains_key(&abbr) {
+                self.words
+                    .entry(abbr)
+                    .or_insert_with(|| full.to_lowercase());
+            }
+        }
+        for (abbr, full) in suffixes {
+            if !self.suffixes.iter().any(|(known, _)| known == abbr) {
+                self.suffixes
+                    .push((abbr.to_lowercase(), full.to_lowercase()));
+            }
+        }
+        // The longest suffix should be tried first: 'hptstr.' before 'str.'
+        self.suffixes
+            .sort_by(|(lhs, _), (rhs, _)| rhs.len().cmp(&lhs.len()).then(lhs.cmp(rhs)));
+    }
+
+    /// Lowercases `text` and expands the abbreviations token by token,
+    /// so only whole words are replaced: 'rt des vignes' -> 'route des vignes', but 'gurt' stays.
+    /// An abbreviation of several languages is expanded in the language of the other words
+    /// ('pl. de la gare' -> 'place de la gare') and is kept as is if the language is unknown
+    pub fn expand(&self, text: &str) -> String {
+        let text = text.to_lowercase();
+        let tokens = text.split_whitespace().collect::<Vec<&str>>();
+        let language = self.language(&tokens);
+        tokens
+            .into_iter()
+            .map(|token| self.expand_token(token, language, false))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// The first language in the order with a marker or a compound word ending
+    /// with one of its endings among `tokens`
+    #[inline]
+    fn language(&self, tokens: &[&str]) -> Option<&str> {
+        self.markers
+            .iter()
+            .find(|(_, markers, endings)| {
+                tokens.iter().any(|token| {
+                    token
+                        .split(|ch: char| !ch.is_alphabetic())
+                        .any(|word| markers.iter().any(|marker| marker == word))
+                        || endings.iter().any(|ending| {
+                            token.len() > ending.len() && token.ends_with(ending.as_str())
+                        })
+                })
+            })
+            .map(|(language, _, _)| language.as_str())
+    }
+
+    /// `is_part` - whether `token` is the last part of a hyphenated word ('jean-jaurès-str.'),
+    /// an ambiguous abbreviation there is still expanded as the end of a compound word.
+    /// The other parts are names or initials ('g.-guisan'), so only the prefixes expand them
+    #[inline]
+    fn expand_token(&self, token: &str, language: Option<&str>, is_part: bool) -> String {
+        // A list separator glued to the word: 'ch.,' or 'str./'
+        let word = token.trim_end_matches([',', ';', '/']);
+        if let Some(languages) = self.ambiguous.get(word) {
+            match languages
+                .iter()
+                .find(|(name, _)| Some(name.as_str()) == language)
+            {
+                Some((_, full)) => return format!("{}{}", full, &token[word.len()..]),
+                None if !is_part => return token.to_string(),
+                None => {}
+            }
+        }
+        if let Some(full) = self.words.get(word) {
+            return format!("{}{}", full, &token[word.len()..]);
+        }
+        if let Some((abbr, full)) = self
+            .prefixes
+            .iter()
+            .find(|(abbr, _)| token.starts_with(abbr.as_str()))
+        {
+            return format!(
+                "{}{}",
+                full,
+                self.expand_token(&token[abbr.len()..], language, true)
+            );
+        }
+        if let Some((head, last)) = token.rsplit_once('-') {
+            return format!("{}-{}", head, self.expand_token(last, language, true));
+        }
+        for (abbr, full) in &self.suffixes {
+            if let Some(stem) = token.strip_suffix(abbr.as_str()) {
+                return format!("{}{}", stem, full);
+            }
+        }
+        token.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_words() {
+        let abbreviations = Abbreviations::builtin();
+        assert_eq!(
+            abbreviations.expand("Ch. des Rottes 33"),
+            "chemin des rottes 33"
+        );
+        assert_eq!(
+            abbreviations.expand("Bd Carl-Vogt 2"),
+            "boulevard carl-vogt 2"
+        );
+        assert_eq!(abbreviations.expand("V. Cantonale 4"), "via cantonale 4");
+        assert_eq!(
+            abbreviations.expand("Rt des Vignes 1B"),
+            "route des vignes 1b"
+        );
+        assert_eq!(
+            abbreviations.expand("Route de St-Légier 15a"),
+            "route de saint-légier 15a"
+        );
+        assert_eq!(
+            abbreviations.expand("Ch. de St. Cergue 11"),
+            "chemin de saint cergue 11"
+        );
+        assert_eq!(abbreviations.expand("Ste-Croix 2"), "sainte-croix 2");
+    }
+
+    #[test]
+    fn keep_initials() {
+        let abbreviations = Abbreviations::builtin();
+        assert_eq!(
+            abbreviations.expand("Av. G.-Guisan 3"),
+            "avenue g.-guisan 3"
+        );
+        assert_eq!(
+            abbreviations.expand("Rue C.-F.-Ramuz 8"),
+            "rue c.-f.-ramuz 8"
+        );
+    }
+
+    #[test]
+    fn expand_suffixes() {
+        let abbreviations = Abbreviations::builtin();
+        assert_eq!(abbreviations.expand("Turnerstr. 22"), "turnerstrasse 22");
+        assert_eq!(abbreviations.expand("Hptstr. 3"), "hauptstrasse 3");
+        assert_eq!(
+            abbreviations.expand("Jean-Jaurès-Str. 3"),
+            "jean-jaurès-strasse 3"
+        );
+    }
+
+    #[test]
+    fn expand_only_whole_words() {
+        let abbreviations = Abbreviations::builtin();
+        assert_eq!(abbreviations.expand("Gurt 5"), "gurt 5");
+        assert_eq!(
+            abbreviations.expand("Chemin L-Hubert 13"),
+            "chemin l-hubert 13"
+        );
+        assert_eq!(abbreviations.expand("Avenue 7"), "avenue 7");
+    }
+
+    #[test]
+    fn language_from_context() {
+        let abbreviations = Abbreviations::builtin();
+        assert_eq!(abbreviations.expand("Bahnhofpl. 1"), "bahnhofpl. 1");
+        assert_eq!(abbreviations.expand("pl. 1"), "pl. 1");
+        assert_eq!(
+            abbreviations.expand("Pl. de la Gare 2"),
+            "place de la gare 2"
+        );
+        assert_eq!(
+            abbreviations.expand("Str. Cantonale 3"),
+            "strada cantonale 3"
+        );
+        assert_eq!(abbreviations.expand("Str. im Feld 3"), "strasse im feld 3");
+        assert_eq!(abbreviations.expand("Bahnhofstr. 1"), "bahnhofstrasse 1");
+        assert_eq!(
+            abbreviations.expand("St. Gallerstrasse 4"),
+            "sankt gallerstrasse 4"
+        );
+        assert_eq!(abbreviations.expand("St. 4"), "st. 4");
+        let abbreviations = Abbreviations::from_toml(
+            r#"
+            order = ["fr", "de"]
+            [de]
+            markers = ["am"]
+            [de.words]
+            "pl." = "platz"
+            [fr]
+            markers = ["la"]
+            [fr.words]
+            "pl." = "place"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(abbreviations.expand("pl. am la 1"), "place am la 1");
+        assert_eq!(abbreviations.expand("pl. am 1"), "platz am 1");
+    }
+
+    #[test]
+    fn invalid_abbreviations() {
+        assert!(matches!(
+            Abbreviations::from_toml("order = [\"de\"]"),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Abbreviations::from_toml("[de.words]\n\"g.\" = 1"),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
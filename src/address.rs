@@ -0,0 +1,472 @@
+//! This module splits a raw Swiss address into typed components:
+//! street name, house number, alphabetic suffix, unit and the leftover text
+use crate::abbreviations::Abbreviations;
+use regex::Regex;
+use std::fmt;
+
+/// Characters trimmed around the leftover text of an address
+const SEPARATORS: &[char] = &[' ', ',', '.', '/', '\\', '-', '+', '–', ';'];
+/// Characters that may follow a single letter in a house number list: '5a,b' or '5a–c'
+const LIST_ENDS: &[char] = &[',', '+', '/', '&', ' ', ';', ')'];
+/// Limit of house numbers produced by a single range, larger ranges are not expanded
+const MAX_RANGE_LEN: u32 = 100;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Address {
+    pub street_name: String,
+    pub house_number: Option<u32>,
+    /// Alphabetic suffix of the house number: 'a', 'b', 'bis', 'ter', ...
+    pub suffix: Option<String>,
+    /// Unit or staircase: 'eingang b', 'escalier 2', ...
+    pub unit: Option<String>,
+    /// Any text left after the house number that is not recognised as a component
+    pub rest: String,
+    /// Building or complex name given in parentheses: '(Gerber-Areal)', '(Stade de la Maladière)'
+    pub building_name: Option<String>,
+}
+
+impl Address {
+    /// The house number together with its suffix, e.g. '4a' or '30bis'
+    pub fn full_number(&self) -> Option<String> {
+        self.house_number
+            .map(|number| format!("{}{}", number, self.suffix.as_deref().unwrap_or_default()))
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.full_number() {
+            Some(number) => write!(f, "{} {}", self.street_name, number),
+            None => f.write_str(&self.street_name),
+        }
+    }
+}
+
+/// Parser of Swiss addresses. Holds precompiled regular expressions,
+/// so one parser should be created once and reused for many addresses.
+#[derive(Debug, Clone)]
+pub struct AddressParser {
+    // Matches: '76 chemin des clos', '76, chemin des clos' or 'a4 résidence du golf'
+    prefix_number: Regex,
+    // Matches the house number and the rest of 'bernstrasse 4a, 5, 6', 'chasseur 30 bis',
+    // 'bernstrasse 4 a fasdfs' from the start of the number, see `house_number_start`
+    street_number: Regex,
+    // Matches: 'a4', '4a' or '30bis' as a separate token
+    number_token: Regex,
+    // Matches the next item of a house number list: '+12', '–19', ', 5a', '–c'
+    list_item: Regex,
+    unit: Regex,
+    // Matches: '(gerber-areal)', '(métropole 2000 i + ii)' or unclosed '(emmenbrücke'
+    annotation: Regex,
+    abbreviations: Abbreviations,
+}
+
+impl Default for AddressParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AddressParser {
+    /// Creates a parser with the builtin abbreviations, see `Abbreviations::builtin`
+    pub fn new() -> Self {
+        Self::with_abbreviations(Abbreviations::builtin())
+    }
+
+    pub fn with_abbreviations(abbreviations: Abbreviations) -> Self {
+        Self {
+            prefix_number: Regex::new(r"^(\w?\d+\w*),?\s+(.+)$").unwrap(),
+            street_number: Regex::new(
+                r"^(?P<number>\d+)(?:\s?(?P<suffix>bis|ter|quater|[a-z])\b)?(?P<rest>.*)$",
+            )
+            .unwrap(),
+            number_token: Regex::new(r"^(?P<prefix>[a-z])?(?P<number>\d+)(?P<suffix>bis|ter|quater|[a-z])?$")
+                .unwrap(),
+            list_item: Regex::new(
+                r"^\s*(?P<sep>[,+/&]|[-–]|\s)\s*(?:(?P<number>\d+)(?:\s?(?P<suffix>bis|ter|quater|[a-z])\b)?|(?P<letter>[a-z])\b)",
+            )
+            .unwrap(),
+            unit: Regex::new(
+                r"\b(?:eingang|stiege|treppenhaus|haus|block|bloc|escalier|esc\.|entrée|appartement|app\.|wohnung|whg\.|scala)\s*\w+",
+            )
+            .unwrap(),
+            annotation: Regex::new(r"\(([^()]*)(?:\)|$)").unwrap(),
+            abbreviations,
+        }
+    }
+
+    pub fn parse(&self, address: &str) -> Address {
+        let (address, building_name) = self.strip_annotations(address);
+        let address = self.abbreviations.expand(&address);
+        let address = self.parse_prefix_number(&address).unwrap_or_else(|| {
+            let (mut address, rest) = self.parse_street_number(&address);
+            address.rest = rest.trim_matches(SEPARATORS).to_string();
+            address
+        });
+        Address {
+            building_name,
+            ..address
+        }
+    }
+
+    /// Cuts the parenthesised annotations out of `address`,
+    /// returning the remaining address and the annotations joined by ', '
+    pub fn strip_annotations(&self, address: &str) -> (String, Option<String>) {
+        let annotations = self
+            .annotation
+            .captures_iter(address)
+            .map(|caps| caps[1].trim().to_string())
+            .filter(|annotation| !annotation.is_empty())
+            .collect::<Vec<String>>();
+        (
+            self.annotation.replace_all(address, " ").trim().to_string(),
+            if annotations.is_empty() {
+                None
+            } else {
+                Some(annotations.join(", "))
+            },
+        )
+    }
+
+    /// Parses an address with a range or a list of house numbers,
+    /// returning a separate address for each house number.
+    ///
+    /// Matches: 'hohenrainstrasse 10+12+14', 'avenue des baumes 17–19',
+    /// 'rue des terreaux 15,17,19', 'günzenenstrasse 5,5a–c'.
+    /// A numeric range keeps the side of the street, so '10-14' expands to 10, 12 and 14
+    pub fn parse_all(&self, address: &str) -> Vec<Address> {
+        let (address, building_name) = self.strip_annotations(address);
+        let address = self.abbreviations.expand(&address);
+        if let Some(address) = self.parse_prefix_number(&address) {
+            return vec![Address {
+                building_name,
+                ..address
+            }];
+        }
+        let (address, rest) = self.parse_street_number(&address);
+        let address = Address {
+            building_name,
+            ..address
+        };
+        let house_number = match address.house_number {
+            Some(house_number) => house_number,
+            None => return vec![address],
+        };
+        let (numbers, rest) = self.expand_numbers((house_number, address.suffix.clone()), &rest);
+        let rest = rest.trim_matches(SEPARATORS).to_string();
+        numbers
+            .into_iter()
+            .map(|(house_number, suffix)| Address {
+                house_number: Some(house_number),
+                suffix,
+                rest: rest.to_owned(),
+                ..address.clone()
+            })
+            .collect()
+    }
+
+    /// Consumes the list items from the start of `text`,
+    /// returning all house numbers and the text left after the list
+    fn expand_numbers<'a>(
+        &self,
+        first: (u32, Option<String>),
+        mut text: &'a str,
+    ) -> (Vec<(u32, Option<String>)>, &'a str) {
+        let mut numbers = vec![first];
+        while let Some(caps) = self.list_item.captures(text) {
+            let (last_number, last_suffix) = numbers.last().cloned().expect("not empty");
+            let is_range = matches!(&caps["sep"], "-" | "–");
+            let rest = &text[caps.get(0).expect("whole match").end()..];
+            if let Some(letter) = caps.name("letter") {
+                // A single letter must end the item, otherwise it is a next word: 'l-hubert'
+                if rest
+                    .chars()
+                    .next()
+                    .is_some_and(|ch| !LIST_ENDS.contains(&ch))
+                {
+                    break;
+                }
+                let letter = letter.as_str().chars().next().expect("matched by regexp");
+                match last_suffix
+                    .as_deref()
+                    .and_then(|suffix| suffix.chars().next())
+                {
+                    Some(from) if is_range && from < letter => numbers.extend(
+                        ((from as u8 + 1)..=(letter as u8))
+                            .map(|ch| (last_number, Some((ch as char).to_string()))),
+                    ),
+                    _ => numbers.push((last_number, Some(letter.to_string()))),
+                }
+            } else {
+                let number = caps["number"].parse::<u32>().unwrap_or_default();
+                let suffix = caps
+                    .name("suffix")
+                    .map(|suffix| suffix.as_str().to_string());
+                if is_range && last_number < number && number - last_number <= MAX_RANGE_LEN {
+                    let step = if (number - last_number) % 2 == 0 {
+                        2
+                    } else {
+                        1
+                    };
+                    numbers.extend(
+                        (last_number + step..number)
+                            .step_by(step as usize)
+                            .map(|number| (number, None)),
+                    );
+                }
+                numbers.push((number, suffix));
+            }
+            text = rest;
+        }
+        (numbers, text)
+    }
+
+    fn parse_prefix_number(&self, address: &str) -> Option<Address> {
+        let caps = self.prefix_number.captures(address)?;
+        let number = self.number_token.captures(&caps[1])?;
+        let (street_name, unit) = self.split_unit(&caps[2]);
+        Some(Address {
+            street_name: street_name.trim_matches(SEPARATORS).to_string(),
+            house_number: number["number"].parse().ok(),
+            suffix: number
+                .name("suffix")
+                .or_else(|| number.name("prefix"))
+                .map(|suffix| suffix.as_str().to_string()),
+            unit,
+            ..Address::default()
+        })
+    }
+
+    /// Returns the address with an empty `rest` and the raw text after the house number
+    fn parse_street_number(&self, address: &str) -> (Address, String) {
+        let number = house_number_start(address).and_then(|start| {
+            Some((
+                &address[..start],
+                self.street_number.captures(&address[start..])?,
+            ))
+        });
+        match number {
+            Some((street_name, caps)) => {
+                let (rest, unit) = self.split_unit(&caps["rest"]);
+                (
+                    Address {
+                        street_name: street_name.trim_matches(SEPARATORS).to_string(),
+                        house_number: caps["number"].parse().ok(),
+                        suffix: caps
+                            .name("suffix")
+                            .map(|suffix| suffix.as_str().to_string()),
+                        unit,
+                        ..Address::default()
+                    },
+                    rest,
+                )
+            }
+            None => (
+                Address {
+                    street_name: address.trim_matches(SEPARATORS).to_string(),
+                    ..Address::default()
+                },
+                String::new(),
+            ),
+        }
+    }
+
+    /// Cuts the unit out of `text`, returning the remaining text and the unit
+    #[inline]
+    fn split_unit(&self, text: &str) -> (String, Option<String>) {
+        match self.unit.find(text) {
+            Some(mat) => (
+                format!("{}{}", &text[..mat.start()], &text[mat.end()..]),
+                Some(mat.as_str().to_string()),
+            ),
+            None => (text.to_string(), None),
+        }
+    }
+}
+
+/// The byte offset of the house number: the first number after the street name which is not
+/// joined to a word by a hyphen, as the dates in 'rue du 31-décembre 8' or 'avenue du 1er-mars 12'
+fn house_number_start(address: &str) -> Option<usize> {
+    let (offsets, chars): (Vec<usize>, Vec<char>) = address.char_indices().unzip();
+    // A single letter after a hyphen is a range of suffixes: '21a-d'
+    let is_word = |chars: &mut dyn Iterator<Item = &char>| {
+        chars.take_while(|ch| ch.is_alphabetic()).count() > 1
+    };
+    (1..chars.len())
+        .find(|&idx| {
+            if !chars[idx].is_ascii_digit() || chars[idx - 1].is_ascii_digit() {
+                return false;
+            }
+            let end = idx
+                + chars[idx..]
+                    .iter()
+                    .take_while(|ch| ch.is_alphanumeric())
+                    .count();
+            let is_joined_after =
+                chars.get(end) == Some(&'-') && is_word(&mut chars[end + 1..].iter());
+            let is_joined_before =
+                chars[idx - 1] == '-' && is_word(&mut chars[..idx - 1].iter().rev());
+            !is_joined_after && !is_joined_before
+        })
+        .map(|idx| offsets[idx])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_parsed(
+        address: &str,
+        street_name: &str,
+        house_number: Option<u32>,
+        suffix: Option<&str>,
+        rest: &str,
+    ) {
+        let parsed = AddressParser::new().parse(address);
+        assert_eq!(parsed.street_name, street_name);
+        assert_eq!(parsed.house_number, house_number);
+        assert_eq!(parsed.suffix.as_deref(), suffix);
+        assert_eq!(parsed.rest, rest);
+    }
+
+    #[test]
+    fn parse_street_number() {
+        assert_parsed("   Bernstrasse 7   ", "bernstrasse", Some(7), None, "");
+        assert_parsed("Rebenstr. 15b", "rebenstrasse", Some(15), Some("b"), "");
+        assert_parsed("Chasseur 30 bis", "chasseur", Some(30), Some("bis"), "");
+        assert_parsed(
+            "Bernstrasse 4 A fasdfs",
+            "bernstrasse",
+            Some(4),
+            Some("a"),
+            "fasdfs",
+        );
+        assert_parsed(
+            "Bernstrasse 4a. 5 6",
+            "bernstrasse",
+            Some(4),
+            Some("a"),
+            "5 6",
+        );
+        assert_parsed(
+            "Löwenstr. 11/Hofplatz",
+            "löwenstrasse",
+            Some(11),
+            None,
+            "hofplatz",
+        );
+        assert_parsed(
+            "ch de saint-cierges 3,fas23dfsfsdf",
+            "chemin de saint-cierges",
+            Some(3),
+            None,
+            "fas23dfsfsdf",
+        );
+    }
+
+    #[test]
+    fn parse_number_in_street_name() {
+        assert_parsed(
+            "Rue du 31-Décembre 8",
+            "rue du 31-décembre",
+            Some(8),
+            None,
+            "",
+        );
+        assert_parsed(
+            "Avenue du 1er-Mars 12",
+            "avenue du 1er-mars",
+            Some(12),
+            None,
+            "",
+        );
+        assert_expanded("Avenue du Bietschhorn 21a-d", &["21a", "21b", "21c", "21d"]);
+    }
+
+    #[test]
+    fn parse_prefix_number() {
+        assert_parsed("76 chemin des clos", "chemin des clos", Some(76), None, "");
+        assert_parsed("76, chemin des clos", "chemin des clos", Some(76), None, "");
+        assert_parsed("a4 Bernstrasse", "bernstrasse", Some(4), Some("a"), "");
+    }
+
+    #[test]
+    fn parse_unit() {
+        let parsed = AddressParser::new().parse("Bernstrasse 7 Eingang B");
+        assert_eq!(parsed.unit.as_deref(), Some("eingang b"));
+        assert_eq!(parsed.rest, "");
+        let parsed = AddressParser::new().parse("Rue du Clos 1 escalier 2");
+        assert_eq!(parsed.unit.as_deref(), Some("escalier 2"));
+    }
+
+    fn assert_expanded(address: &str, expected: &[&str]) {
+        assert_eq!(
+            AddressParser::new()
+                .parse_all(address)
+                .iter()
+                .map(|address| address.full_number().unwrap())
+                .collect::<Vec<String>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn expand_house_numbers() {
+        assert_expanded("Hohenrainstrasse 10+12+14", &["10", "12", "14"]);
+        assert_expanded("Hohenrainstr. 10-14", &["10", "12", "14"]);
+        assert_expanded("Avenue des Baumes 17–19", &["17", "19"]);
+        assert_expanded("Rue des Terreaux 15,17,19", &["15", "17", "19"]);
+        assert_expanded("Günzenenstrasse 5,5a–c", &["5", "5a", "5b", "5c"]);
+        assert_expanded("Rue Pestalozzi 23+23bis", &["23", "23bis"]);
+        assert_expanded(
+            "Avenue du Bietschhorn 21a–d,23a–b",
+            &["21a", "21b", "21c", "21d", "23a", "23b"],
+        );
+        assert_expanded(
+            "Chemin Bosquets de Paudille 15, 16, 17",
+            &["15", "16", "17"],
+        );
+        assert_expanded("Pierre-à-Mazel 4/6", &["4", "6"]);
+        assert_expanded("Bernstrasse 7", &["7"]);
+    }
+
+    #[test]
+    fn expand_stops_at_next_word() {
+        let addresses = AddressParser::new().parse_all("Avenue des Morgines 18, L-Hubert");
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[0].rest, "l-hubert");
+        let addresses = AddressParser::new().parse_all("Löwenstr. 11/Hofplatz");
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[0].rest, "hofplatz");
+    }
+
+    #[test]
+    fn parse_building_name() {
+        let parser = AddressParser::new();
+        let parsed = parser.parse("Rue de la Pierre-à-Mazel 10 (Stade de la Maladière)");
+        assert_eq!(parsed.street_name, "rue de la pierre-à-mazel");
+        assert_eq!(parsed.house_number, Some(10));
+        assert_eq!(parsed.rest, "");
+        assert_eq!(
+            parsed.building_name.as_deref(),
+            Some("Stade de la Maladière")
+        );
+        let parsed = parser.parse("Mühlematt 7-11 (Emmenbrücke");
+        assert_eq!(parsed.building_name.as_deref(), Some("Emmenbrücke"));
+        let addresses = parser.parse_all("Rue des Terreaux 15,17,19 (Métropole 2000 I + II)");
+        assert_eq!(addresses.len(), 3);
+        assert_eq!(
+            addresses[2].building_name.as_deref(),
+            Some("Métropole 2000 I + II")
+        );
+        assert_eq!(parser.parse("Bernstrasse 7").building_name, None);
+    }
+
+    #[test]
+    fn display_address() {
+        assert_eq!(
+            AddressParser::new().parse("Chasseur 30 bis").to_string(),
+            "chasseur 30bis"
+        );
+    }
+}
@@ -1,9 +1,10 @@
 //! This module creates an object that represents the candidate text to the target text
+use crate::{
+    abbreviations::Abbreviations, mass::Error, normalization::Normalization, text_sim::SimAlgo,
+};
 use std::{
-    cmp,
-    cmp::{Ordering, PartialEq},
-    error::Error as StdError,
-    fmt, io, process,
+    cmp::{Ordering, PartialEq, Reverse},
+    collections::BinaryHeap,
 };
 
 pub(crate) const PUNCTUATIONS: &[char] = &[
@@ -11,73 +12,66 @@ pub(crate) const PUNCTUATIONS: &[char] = &[
 ];
 const SENS: f64 = 0.7;
 
-pub type SimResult = Result<Vec<Candidate>, Error>;
-
-#[derive(Debug)]
-pub enum Error {
-    Io(io::Error),
-    NotFound,
-}
-
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::NotFound => f.write_str("CandidatesNotFound"),
-            Self::Io(err) => f.write_str(&err.to_string()),
-        }
-    }
-}
-
-impl StdError for Error {
-    fn description(&self) -> &str {
-        match *self {
-            Error::Io(_) => "Io Error",
-            Error::NotFound => "Candidate Not Found",
-        }
-    }
-}
-
-impl From<io::Error> for Error {
-    fn from(err: io::Error) -> Self {
-        Self::Io(err)
-    }
-}
+pub type SimResult<P = ()> = Result<Vec<Candidate<P>>, Error>;
 
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Text {
     pub init: String,
     pub cleaned: String,
+    /// The words of the normalised text separated by a space, used by the token-based algorithms
+    pub words: String,
 }
 
 impl Text {
+    /// The text is normalised with `Normalization::default()` before cleaning,
+    /// `init` keeps the original text
     pub fn new(text: String) -> Self {
+        Self::with(text, &Normalization::default(), None)
+    }
+
+    /// The same as `Text::new`, but the abbreviations are expanded before cleaning:
+    /// 'Ch. des Rottes' is compared as 'chemindesrottes'
+    pub fn with_abbreviations(text: String, abbreviations: &Abbreviations) -> Self {
+        Self::with(text, &Normalization::default(), Some(abbreviations))
+    }
+
+    /// Normalises `text` with `normalization` and expands the `abbreviations` if set,
+    /// the similarity is computed on the result
+    pub fn with(
+        text: String,
+        normalization: &Normalization,
+        abbreviations: Option<&Abbreviations>,
+    ) -> Self {
+        let normalized = normalization.apply(&text);
+        let expanded = match abbreviations {
+            Some(abbreviations) => abbreviations.expand(&normalized),
+            None => normalized.to_lowercase(),
+        };
         Self {
-            cleaned: text.to_lowercase().replace(PUNCTUATIONS, ""),
+            cleaned: expanded.replace(PUNCTUATIONS, ""),
+            words: expanded
+                .split(PUNCTUATIONS)
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<&str>>()
+                .join(" "),
             init: text,
         }
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct Sens(pub f64);
 
 impl Sens {
-    pub fn new(sens: f64) -> Self {
-        if 1.0 - sens < 0.0 {
-            eprintln!(
-                "Sensitivity should be lower or equal than 1.0, but the value was {}",
-                sens
-            );
-            process::exit(1);
-        }
-        if sens - 1e-10 < 0.0 {
-            eprintln!(
-                "Sensitivity should be larger or equal than 0.0, but the value was {}",
-                sens
-            );
-            process::exit(1);
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidSens` if `sens` is not larger than 0.0 or is larger than 1.0,
+    /// as well as if it is NaN
+    pub fn new(sens: f64) -> Result<Self, Error> {
+        if !(sens > 0.0 && sens <= 1.0) {
+            return Err(Error::InvalidSens(sens));
         }
-        Self(sens)
+        Ok(Self(sens))
     }
 }
 
@@ -87,41 +81,209 @@ impl Default for Sens {
     }
 }
 
+/// A matched candidate, `P` is the data carried along with its text
+/// (e.g. the other columns of its line), `()` if there is none
+///
+/// The candidates are compared over all the fields: the similarity first, so the greater
+/// candidate is the more similar one, then the texts, the position, the algorithm,
+/// the components and the payload. The scores are compared by `f64::total_cmp`,
+/// so a NaN equals itself and the equality agrees with the ordering.
 #[derive(Debug, Clone, Default)]
-pub struct Candidate {
+pub struct Candidate<P = ()> {
     pub text: String,
+    /// The text after the normalisation and the expansion of abbreviations, see `Text::cleaned`
+    pub cleaned: String,
     pub similarity: f64,
+    /// The score of each algorithm if the similarity was combined by `Ensemble`
+    pub components: Vec<(SimAlgo, f64)>,
+    /// The line of the file or the position in the list the candidate was taken from
+    pub index: Option<usize>,
+    /// The algorithm which scored the candidate, `None` for a combined or a custom similarity
+    pub algo: Option<SimAlgo>,
+    pub payload: P,
 }
 
 impl Candidate {
     pub fn from(text: &str) -> Self {
         Self {
             text: text.to_owned(),
-            similarity: 0.0,
+            ..Default::default()
+        }
+    }
+}
+
+impl<P> Candidate<P> {
+    /// The same candidate carrying `payload`
+    pub fn with_payload<Q>(self, payload: Q) -> Candidate<Q> {
+        Candidate {
+            text: self.text,
+            cleaned: self.cleaned,
+            similarity: self.similarity,
+            components: self.components,
+            index: self.index,
+            algo: self.algo,
+            payload,
+        }
+    }
+
+    /// Compares all the fields but the payload
+    fn cmp_scored(&self, other: &Self) -> Ordering {
+        self.similarity
+            .total_cmp(&other.similarity)
+            .then_with(|| self.text.cmp(&other.text))
+            .then_with(|| self.cleaned.cmp(&other.cleaned))
+            .then_with(|| self.index.cmp(&other.index))
+            .then_with(|| self.algo.cmp(&other.algo))
+            .then_with(|| {
+                self.components
+                    .iter()
+                    .zip(&other.components)
+                    .map(|((lhs_algo, lhs), (rhs_algo, rhs))| {
+                        lhs_algo.cmp(rhs_algo).then(lhs.total_cmp(rhs))
+                    })
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or_else(|| self.components.len().cmp(&other.components.len()))
+            })
+    }
+}
+
+impl<P: PartialEq> PartialEq for Candidate<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp_scored(other).is_eq() && self.payload == other.payload
+    }
+}
+
+impl<P: Eq> Eq for Candidate<P> {}
+
+impl<P: PartialOrd> PartialOrd for Candidate<P> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.cmp_scored(other) {
+            Ordering::Equal => self.payload.partial_cmp(&other.payload),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+impl<P: Ord> Ord for Candidate<P> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_scored(other)
+            .then_with(|| self.payload.cmp(&other.payload))
+    }
+}
+
+/// The candidate with its position in the input, the earlier one wins a tie
+#[derive(Debug)]
+struct Ranked<P> {
+    order: usize,
+    candidate: Candidate<P>,
+}
+
+impl<P> Ranked<P> {
+    /// NaN is ranked below every other similarity
+    #[inline]
+    fn similarity(&self) -> f64 {
+        if self.candidate.similarity.is_nan() {
+            f64::NEG_INFINITY
+        } else {
+            self.candidate.similarity
         }
     }
 }
 
-impl PartialEq for Candidate {
+impl<P> PartialEq for Ranked<P> {
     fn eq(&self, other: &Self) -> bool {
-        self.text == other.text
+        self.cmp(other) == Ordering::Equal
     }
 }
 
-impl PartialOrd for Candidate {
+impl<P> Eq for Ranked<P> {}
+
+impl<P> PartialOrd for Ranked<P> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.similarity.partial_cmp(&other.similarity)
+        Some(self.cmp(other))
     }
 }
 
+/// The better candidate is the greater one
+impl<P> Ord for Ranked<P> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity()
+            .total_cmp(&other.similarity())
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+/// Bounded selection of the `num_to_keep` best candidates: the worst kept candidate is
+/// on top of a min-heap, so each push costs `O(log num_to_keep)` and nothing else is stored
+#[derive(Debug)]
+pub(crate) struct TopK<P = ()> {
+    num_to_keep: usize,
+    heap: BinaryHeap<Reverse<Ranked<P>>>,
+    num_of_pushed: usize,
+}
+
+impl<P> TopK<P> {
+    pub(crate) fn new(num_to_keep: usize) -> Self {
+        Self {
+            num_to_keep,
+            heap: BinaryHeap::with_capacity(num_to_keep.min(1024) + 1),
+            num_of_pushed: 0,
+        }
+    }
+
+    /// `order` is the position of the candidate in the input, used to break the ties
+    #[inline]
+    pub(crate) fn push(&mut self, order: usize, candidate: Candidate<P>) {
+        self.num_of_pushed += 1;
+        let ranked = Ranked { order, candidate };
+        if self.heap.len() < self.num_to_keep {
+            self.heap.push(Reverse(ranked));
+        } else if let Some(mut worst) = self.heap.peek_mut() {
+            if ranked > worst.0 {
+                *worst = Reverse(ranked);
+            }
+        }
+    }
+
+    /// Merges the candidates kept by another selection, e.g. of another worker
+    pub(crate) fn merge(&mut self, other: Self) {
+        let num_of_pushed = self.num_of_pushed + other.num_of_pushed;
+        for Reverse(ranked) in other.heap {
+            self.push(ranked.order, ranked.candidate);
+        }
+        self.num_of_pushed = num_of_pushed;
+    }
+
+    /// The kept candidates, the best first
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if no candidate was pushed
+    pub(crate) fn into_sorted(self) -> SimResult<P> {
+        if self.num_of_pushed == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(self
+            .heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(ranked)| ranked.candidate)
+            .collect())
+    }
+}
+
+/// The `num_to_keep` best candidates in the order of the similarity,
+/// the ties are kept in the order of `candidates`
 #[inline]
-pub(crate) fn try_sort_and_keep(values: &mut Vec<Candidate>, num_to_keep: usize) -> SimResult {
-    if !values.is_empty() {
-        values.sort_by(|lhs, rhs| rhs.partial_cmp(lhs).unwrap());
-        Ok(values[..cmp::min(num_to_keep, values.len())].to_vec())
-    } else {
-        Err(Error::NotFound)
+pub(crate) fn keep_best<P>(
+    candidates: impl IntoIterator<Item = Candidate<P>>,
+    num_to_keep: usize,
+) -> SimResult<P> {
+    let mut top = TopK::new(num_to_keep);
+    for (order, candidate) in candidates.into_iter().enumerate() {
+        top.push(order, candidate);
     }
+    top.into_sorted()
 }
 
 #[cfg(test)]
@@ -129,14 +291,141 @@ mod tests {
     use super::*;
 
     #[test]
-    #[should_panic(expected = "should be larger or equal than")]
+    fn text_with_abbreviations() {
+        let abbreviations = Abbreviations::builtin();
+        assert_eq!(
+            Text::with_abbreviations("Ch. des Rottes".to_string(), &abbreviations),
+            Text {
+                init: "Ch. des Rottes".to_string(),
+                cleaned: "chemindesrottes".to_string(),
+                words: "chemin des rottes".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn text_keeps_original() {
+        let text = Text::new("Große  Gasse".to_string());
+        assert_eq!(text.init, "Große  Gasse");
+        assert_eq!(text.cleaned, "grossegasse");
+        assert_eq!(
+            Text::new("Zu\u{308}rich".to_string()).cleaned,
+            Text::new("Zürich".to_string()).cleaned
+        );
+    }
+
+    fn candidate(text: &str, similarity: f64) -> Candidate {
+        Candidate {
+            similarity,
+            ..Candidate::from(text)
+        }
+    }
+
+    #[test]
+    fn keep_best_candidates() {
+        let best = keep_best(
+            [
+                candidate("a", 0.5),
+                candidate("b", 0.9),
+                candidate("c", 0.7),
+                candidate("d", 0.9),
+                candidate("e", 0.1),
+            ],
+            3,
+        )
+        .unwrap();
+        assert_eq!(
+            best,
+            vec![
+                candidate("b", 0.9),
+                candidate("d", 0.9),
+                candidate("c", 0.7)
+            ]
+        );
+        assert_eq!(keep_best([candidate("a", 0.5)], 0).unwrap(), vec![]);
+        assert!(matches!(keep_best::<()>([], 3), Err(Error::NotFound)));
+    }
+
+    #[test]
+    fn keep_best_with_nan() {
+        let best = keep_best(
+            [
+                candidate("a", f64::NAN),
+                candidate("b", 0.1),
+                candidate("c", f64::NAN),
+            ],
+            2,
+        )
+        .unwrap();
+        assert_eq!(best, vec![candidate("b", 0.1), candidate("a", f64::NAN)]);
+    }
+
+    #[test]
+    fn merge_keeps_input_order() {
+        let mut first = TopK::new(2);
+        first.push(3, candidate("d", 0.5));
+        let mut second = TopK::new(2);
+        second.push(1, candidate("b", 0.5));
+        second.push(2, candidate("c", 0.5));
+        first.merge(second);
+        assert_eq!(
+            first.into_sorted().unwrap(),
+            vec![candidate("b", 0.5), candidate("c", 0.5)]
+        );
+    }
+
+    #[test]
+    fn compare_all_fields() {
+        assert_ne!(candidate("a", 0.5), candidate("a", 0.6));
+        assert_ne!(
+            candidate("a", 0.5),
+            Candidate {
+                index: Some(1),
+                ..candidate("a", 0.5)
+            }
+        );
+        assert_eq!(candidate("a", f64::NAN), candidate("a", f64::NAN));
+        assert!(candidate("b", 0.5) < candidate("a", 0.6));
+        assert!(candidate("a", 0.5) < candidate("b", 0.5));
+        let with_components = |components: Vec<(SimAlgo, f64)>| Candidate {
+            components,
+            ..candidate("a", 0.5)
+        };
+        assert!(
+            with_components(vec![(SimAlgo::Jaro, 0.5)])
+                < with_components(vec![(SimAlgo::Jaro, 0.5), (SimAlgo::Levenshtein, 0.1)])
+        );
+        assert_ne!(
+            candidate("a", 0.5).with_payload(1),
+            candidate("a", 0.5).with_payload(2)
+        );
+        assert_eq!(
+            candidate("a", 0.5)
+                .with_payload(1)
+                .cmp(&candidate("a", 0.5).with_payload(2)),
+            Ordering::Less
+        );
+    }
+
+    #[test]
     fn sensitivity_lower_than_zero() {
-        Sens::new(-1.0);
+        assert!(matches!(Sens::new(-1.0), Err(Error::InvalidSens(_))));
+        assert!(matches!(Sens::new(0.0), Err(Error::InvalidSens(_))));
+        assert!(Sens::new(1e-11).is_ok());
+    }
+
+    #[test]
+    fn sensitivity_nan() {
+        assert!(matches!(Sens::new(f64::NAN), Err(Error::InvalidSens(_))));
     }
 
     #[test]
-    #[should_panic(expected = "should be lower or equal than")]
     fn sensitivity_larger_than_one() {
-        Sens::new(1.1);
+        assert!(matches!(Sens::new(1.1), Err(Error::InvalidSens(_))));
+    }
+
+    #[test]
+    fn sensitivity_in_range() {
+        assert!(Sens::new(0.5).is_ok());
     }
 }
@@ -2,10 +2,25 @@
 //! more than 2 millions of lines, especially for streets in Switzerland.
 //!
 //! It serves as my first Rust project used for work and published out to the people
+//!
+//! A line of a candidates file which is not valid UTF-8 is skipped,
+//! any other read error stops the lookup with `Error::Io`
+pub mod abbreviations;
+pub mod address;
 mod candidate;
 pub mod mass;
+pub mod normalization;
 pub mod text_sim;
 
+pub use abbreviations::Abbreviations;
+pub use address::{Address, AddressParser};
 pub use candidate::{Candidate, Sens, Text};
-pub use mass::{Place, Plz, Street, StreetConfig};
-pub use text_sim::{Config, SimAlgo};
+pub use mass::{
+    DataPaths, Error, NumberMatch, Place, Plz, SplitMatch, Street, StreetConfig, StreetIndex,
+    StreetMatch,
+};
+pub use normalization::{Normalization, UnicodeForm};
+pub use text_sim::{
+    Aggregation, BkTreeIndex, Config, Ensemble, Executor, FileFormat, MatchEngine, PreparedQuery,
+    Segmentation, Segmented, SimAlgo, SimFunc, Similarity, SymSpellIndex, TrigramIndex,
+};
@@ -1,36 +1,267 @@
 //! This module provides matching on official Switzerland streets
 
 use crate::{
-    candidate::{Candidate, Error as CandidateError, Sens, SimResult, Text},
-    text_sim::{self, Config, SimAlgo},
+    address::{Address, AddressParser},
+    candidate::{self, Candidate, Sens, SimResult, Text},
+    text_sim::{self, Config, SimAlgo, Similarity, TrigramIndex},
 };
 
-use regex::Regex;
-use std::{fs, io, path::PathBuf, process};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error as StdError,
+    fmt,
+    fs::{self, File},
+    io::{self, prelude::*, BufReader},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use toml::Value;
 
 const PLACE_SENS: f64 = 0.6;
+// Share of the street name similarity in the address similarity, the rest is the house number
+const STREET_NAME_WEIGHT: f64 = 0.8;
+// House number similarity if the numbers are equal, but the suffixes are not: '4' and '4a'
+const SUFFIX_MISMATCH_SIM: f64 = 0.5;
 const NUM_TO_KEEP_FILTERED_STREETS: usize = 500;
 const ALGO_TO_FILTER_STREETS: SimAlgo = SimAlgo::Jaro;
-const PATH_TO_PLACES: &str = "./test_data/places.txt";
-const PATH_TO_STREET_NAMES: &str = "./test_data/streets_data/street_names.txt";
-const PATH_TO_STREETS_DATA: &str = "./test_data/streets_data";
+const DEFAULT_DATA_ROOT: &str = "./test_data";
+const DATA_ROOT_ENV: &str = "MASS_DATA_ROOT";
+const PLACES_FILE: &str = "places.txt";
+const STREETS_DATA_DIR: &str = "streets_data";
+const STREET_NAMES_FILE: &str = "street_names.txt";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    DoesNotContainNumbers(String),
+    InvalidSens(f64),
+    UnknownLocation(String),
+    InvalidConfig(String),
+    /// A row of a candidates file does not fit its format, the line is counted from 0
+    /// as `Candidate::index` of the rows
+    InvalidRow(usize, String),
+    MissingData(PathBuf),
+    /// A worker of a parallel comparison stopped before handing back its result,
+    /// e.g. the scorer has panicked or the executor dropped the job
+    Aborted,
+    NotFound,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => f.write_str(&err.to_string()),
+            Self::Toml(err) => f.write_str(&err.to_string()),
+            Self::DoesNotContainNumbers(street) => write!(
+                f,
+                "Argument 'street' must contain street number! Got: '{}'",
+                street
+            ),
+            Self::InvalidSens(sens) => write!(
+                f,
+                "Sensitivity should be in range (0.0, 1.0], but the value was {}",
+                sens
+            ),
+            Self::UnknownLocation(location) => write!(f, "Unknown location: '{}'", location),
+            Self::InvalidConfig(reason) => write!(f, "Invalid data config: {}", reason),
+            Self::InvalidRow(line, reason) => write!(
+                f,
+                "Invalid row at line {} (counted from 0): {}",
+                line, reason
+            ),
+            Self::MissingData(path) => write!(f, "Missing data: '{}'", path.display()),
+            Self::Aborted => f.write_str("Comparison aborted: a worker stopped before its result"),
+            Self::NotFound => f.write_str("CandidatesNotFound"),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Toml(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Toml(err)
+    }
+}
+
+/// Locations of the streets data on disk.
+///
+/// The expected layout of the data root is:
+///
+/// ```text
+/// <root>/places.txt
+/// <root>/streets_data/street_names.txt
+/// <root>/streets_data/<street name>.toml
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataPaths {
+    pub places: PathBuf,
+    pub street_names: PathBuf,
+    pub streets_data: PathBuf,
+}
+
+impl Default for DataPaths {
+    fn default() -> Self {
+        Self::new(DEFAULT_DATA_ROOT)
+    }
+}
+
+impl DataPaths {
+    /// Builds the paths from the data root with the default layout
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        let streets_data = root.as_ref().join(STREETS_DATA_DIR);
+        Self {
+            places: root.as_ref().join(PLACES_FILE),
+            street_names: streets_data.join(STREET_NAMES_FILE),
+            streets_data,
+        }
+    }
+
+    /// Builds the paths from the data root stored in the `MASS_DATA_ROOT` environment variable
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidConfig` if the variable is not set
+    pub fn from_env() -> Result<Self, Error> {
+        env::var_os(DATA_ROOT_ENV)
+            .map(Self::new)
+            .ok_or_else(|| Error::InvalidConfig(format!("'{}' is not set", DATA_ROOT_ENV)))
+    }
+
+    /// Reads the paths from a TOML config file.
+    /// The `root` key sets the data root, while `places`, `street_names` and `streets_data`
+    /// keys override the separate paths. Relative paths are resolved against the config directory.
+    ///
+    /// ```toml
+    /// root = "/data/mass"
+    /// places = "/data/places.txt"
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read or parsed,
+    /// or `Error::InvalidConfig` if the config does not point to any data
+    pub fn from_config(config: &Path) -> Result<Self, Error> {
+        let values = toml::from_str::<Value>(&fs::read_to_string(config)?)?;
+        let base = config.parent().unwrap_or_else(|| Path::new(""));
+        let get = |key: &str| -> Result<Option<PathBuf>, Error> {
+            match values.get(key) {
+                Some(value) => value
+                    .as_str()
+                    .map(|path| Some(base.join(path)))
+                    .ok_or_else(|| Error::InvalidConfig(format!("'{}' must be a string", key))),
+                None => Ok(None),
+            }
+        };
+        let (root, places, street_names, streets_data) = (
+            get("root")?,
+            get("places")?,
+            get("street_names")?,
+            get("streets_data")?,
+        );
+        let paths = match root {
+            Some(root) => Self::new(root),
+            None if places.is_some() && street_names.is_some() && streets_data.is_some() => {
+                Self::new("")
+            }
+            None => {
+                return Err(Error::InvalidConfig(format!(
+                    "'{}' must set 'root' or all of 'places', 'street_names' and 'streets_data'",
+                    config.display()
+                )))
+            }
+        };
+        Ok(Self {
+            places: places.unwrap_or(paths.places),
+            street_names: street_names.unwrap_or(paths.street_names),
+            streets_data: streets_data.unwrap_or(paths.streets_data),
+        })
+    }
+
+    /// Checks that the data layout is complete:
+    /// the places and street names files exist and each street name has its own table
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MissingData` with the first path that does not exist
+    pub fn validate(&self) -> Result<(), Error> {
+        for file in [&self.places, &self.street_names] {
+            if !file.is_file() {
+                return Err(Error::MissingData(file.to_owned()));
+            }
+        }
+        if !self.streets_data.is_dir() {
+            return Err(Error::MissingData(self.streets_data.to_owned()));
+        }
+        for street_name in read_lines(&self.street_names)? {
+            let file = self.street_file(&street_name);
+            if !file.is_file() {
+                return Err(Error::MissingData(file));
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn street_file(&self, street_name: &str) -> PathBuf {
+        self.streets_data
+            .join(format!("{}.toml", street_name.replace('/', "%2C")))
+    }
+}
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Place(pub(crate) String);
 
 impl Place {
-    pub fn new(value: &str) -> Self {
-        let cfg = Config::new(Sens::new(PLACE_SENS), 1, SimAlgo::JaroWinkler, None);
-        Self(
+    /// # Errors
+    ///
+    /// Returns `Error::UnknownLocation` if `value` did not match any place from `places.txt`
+    pub fn new(value: &str, paths: &DataPaths) -> Result<Self, Error> {
+        Self::from_matches(
             text_sim::fast_cmp_with_file(
                 &Text::new(value.to_string()),
-                &PathBuf::from(PATH_TO_PLACES),
-                &cfg,
-            )
-            .map_or(String::new(), |candidates| candidates[0].text.clone()),
+                &paths.places,
+                &Self::config()?,
+            ),
+            value,
         )
     }
+
+    #[inline]
+    fn config() -> Result<Config, Error> {
+        Ok(Config::new(
+            Sens::new(PLACE_SENS)?,
+            1,
+            SimAlgo::JaroWinkler,
+            None,
+        ))
+    }
+
+    #[inline]
+    fn from_matches(matches: SimResult, value: &str) -> Result<Self, Error> {
+        match matches {
+            Ok(candidates) => Ok(Self(candidates[0].text.clone())),
+            Err(Error::NotFound) => Err(Error::UnknownLocation(value.to_string())),
+            Err(err) => Err(err),
+        }
+    }
 }
 
 impl ToString for Place {
@@ -55,44 +286,55 @@ impl Plz {
 }
 
 #[inline]
-fn filter_distant_streets(street: &Text, cfg: &Config) -> Vec<String> {
-    text_sim::fast_cmp_with_file(street, &PathBuf::from(PATH_TO_STREET_NAMES), cfg)
+fn filter_config(cfg: &Config) -> Config {
+    Config {
+        num_to_keep: NUM_TO_KEEP_FILTERED_STREETS,
+        similarity: Arc::new(ALGO_TO_FILTER_STREETS),
+        ..cfg.clone()
+    }
+}
+
+#[inline]
+fn filter_distant_streets(matches: SimResult) -> Vec<String> {
+    matches
         .unwrap_or_default()
         .into_iter()
         .map(|c| c.text)
         .collect()
 }
 
+/// Rescores the shortlist of `Config::candidate_index` if it was built from the street names,
+/// otherwise the streets most similar by Jaro
 #[inline]
-fn find_street_name(street: &Text, cfg: &Config) -> SimResult {
-    let filter_cfg = Config {
-        num_to_keep: NUM_TO_KEEP_FILTERED_STREETS,
-        sim_func: ALGO_TO_FILTER_STREETS.into(),
-        ..*cfg
-    };
-    text_sim::cmp_with_arr(&filter_distant_streets(street, &filter_cfg), street, cfg)
-}
-
-// #[derive(Debug)]
-// pub enum Error {
-//     Io(io::Error),
-//     CError(CandidateError),
-//     DoesNotContainNumbers,
-// }
-
-// impl fmt::Display for Error {
-//     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//         match self {
-//             Self::CError(err) => f.write_str(&err.to_string()),
-//             Self::Io(err) => f.write_str(&err.to_string()),
-//             Self::DoesNotContainNumbers => f.write_str("DoesNotContainNumbers"),
-//         }
-//     }
-// }
+fn find_street_name(street: &Text, cfg: &Config, paths: &DataPaths) -> SimResult {
+    if let Some(index) = cfg
+        .candidate_index
+        .as_deref()
+        .filter(|index| index.source() == Some(paths.street_names.as_path()))
+    {
+        return text_sim::cmp_with_index(street, index, cfg);
+    }
+    let street_names = filter_distant_streets(text_sim::fast_cmp_with_file(
+        street,
+        &paths.street_names,
+        &filter_config(cfg),
+    ));
+    text_sim::cmp_with_arr(&street_names, street, cfg)
+}
+
+#[inline]
+fn read_lines(path: &Path) -> Result<Vec<String>, Error> {
+    Ok(BufReader::new(File::open(path)?)
+        .lines()
+        .collect::<io::Result<Vec<String>>>()?)
+}
 
 #[derive(Debug)]
 pub struct Street {
     pub value: Text,
+    pub address: Address,
+    /// Every address of a house number range or list, the first one is `address`
+    pub addresses: Vec<Address>,
     pub file: StreetFile,
 }
 
@@ -103,59 +345,42 @@ impl PartialEq for Street {
 }
 
 impl Street {
-    pub fn new(street: &str, cfg: Option<Config>) -> Result<Self, CandidateError> {
-        // TODO: add here Error spreading instead of exiting
-        if !Self::contains_numbers(street) {
-            eprintln!(
-                "Argument 'street' must contain street number! Got: '{}'",
-                street
-            );
-            process::exit(1);
-        }
-        let street = Text::new(Self::clean(street));
+    /// # Errors
+    ///
+    /// Returns `Error::DoesNotContainNumbers` if `street` does not contain a number
+    /// (as each valid street MUST contain an any number) and `Error::NotFound`
+    /// if no official street name is similar enough to `street`
+    pub fn new(street: &str, cfg: Option<Config>, paths: &DataPaths) -> Result<Self, Error> {
+        let addresses = Self::parse(street, &AddressParser::new())?;
+        let address = addresses[0].to_owned();
         Ok(Self {
-            file: StreetFile::new(&find_street_name(&street, &cfg.unwrap_or_default())?[0].text)?,
-            value: street,
+            file: StreetFile::new(
+                &find_street_name(
+                    &Text::new(address.street_name.to_owned()),
+                    &cfg.unwrap_or_default(),
+                    paths,
+                )?[0]
+                    .text,
+                paths,
+            )?,
+            value: Text::new(address.to_string()),
+            address,
+            addresses,
         })
     }
 
     #[inline]
-    pub(crate) fn contains_numbers(street: &str) -> bool {
-        street.chars().filter(|ch| ch.is_numeric()).count() > 0
-    }
-
-    #[inline]
-    pub(crate) fn clean(street: &str) -> String {
-        let mut street = street
-            .trim()
-            .to_lowercase()
-            .replace("str.", "strasse")
-            .replace("av. ", "avenue ")
-            .replace("rt ", "route ")
-            .replace("st.", "saint")
-            .replace("st-", "saint");
-        // Matches: '76 chemin des clos' or 'a4 résidence du golf'
-        if Self::starts_with_number(&street) {
-            let (num, street_name) = street.split_once(' ').expect("matched by regexp");
-            street = format!("{} {}", street_name, num);
-        }
-        // Matches: eisfeldstrasse 21/23, milchstrasse 2-10a, milchstrasse 2,10a, bernstrasse 7 8
-        match Regex::new(r"(.*?\s\d*?\s?[a-zA-Z]?)[\./,\-\+\s–\\]")
-            .unwrap()
-            .find(&street)
-        {
-            // but not bernstrasse 7 A
-            Some(mat) if !Regex::new(r"\s\d*?\s[a-zA-Z]$").unwrap().is_match(&street) => {
-                mat.as_str().to_string()
-            }
-            _ => street,
+    fn parse(street: &str, parser: &AddressParser) -> Result<Vec<Address>, Error> {
+        // The number of a building name does not count: 'allée du communet (métropole 2000)'
+        if !Self::contains_numbers(&parser.strip_annotations(street).0) {
+            return Err(Error::DoesNotContainNumbers(street.to_string()));
         }
+        Ok(parser.parse_all(street))
     }
 
     #[inline]
-    pub(crate) fn starts_with_number(street: &str) -> bool {
-        Regex::new(r"^\d+,?\s.+").unwrap().is_match(street)
-            || Regex::new(r"^\w\d+,?\s").unwrap().is_match(street)
+    pub(crate) fn contains_numbers(street: &str) -> bool {
+        street.chars().filter(|ch| ch.is_numeric()).count() > 0
     }
 }
 
@@ -165,14 +390,9 @@ pub struct StreetFile {
 }
 
 impl StreetFile {
-    pub fn new(street_name: &str) -> io::Result<Self> {
-        let filename = format!(
-            "{}/{}.toml",
-            PATH_TO_STREETS_DATA,
-            street_name.replace('/', "%2C")
-        );
+    pub fn new(street_name: &str, paths: &DataPaths) -> Result<Self, Error> {
         Ok(Self {
-            values: toml::from_str::<Value>(&fs::read_to_string(filename)?)?,
+            values: toml::from_str::<Value>(&fs::read_to_string(paths.street_file(street_name))?)?,
         })
     }
 
@@ -212,6 +432,7 @@ impl StreetFile {
     }
 }
 
+#[derive(Clone)]
 pub struct StreetConfig<T> {
     location: Option<T>,
     cfg: Config,
@@ -227,11 +448,17 @@ impl<T> Default for StreetConfig<T> {
 }
 
 impl<T> StreetConfig<T> {
-    pub fn new(location: Option<T>, sens: f64, num_to_keep: usize, algo: SimAlgo) -> Self {
-        Self {
+    /// `similarity` - a `SimAlgo`, an `Ensemble` or any other `Similarity`
+    pub fn new<S: Similarity + 'static>(
+        location: Option<T>,
+        sens: f64,
+        num_to_keep: usize,
+        similarity: S,
+    ) -> Result<Self, Error> {
+        Ok(Self {
             location,
-            cfg: Config::new(Sens::new(sens), num_to_keep, algo, None),
-        }
+            cfg: Config::new(Sens::new(sens)?, num_to_keep, similarity, None),
+        })
     }
 
     pub fn default_with(location: T) -> Self {
@@ -259,41 +486,201 @@ impl<T> StreetConfig<T> {
 /// # Examples
 ///
 /// ```rust
-/// # use street_sim_rs::{Plz, Street, street_sim};
+/// # use street_sim_rs::{DataPaths, Plz, Street, street_sim};
 /// #
 /// # fn main() {
-/// #     let street = Street::new("qu du seujet 36", None).unwrap();
+/// #     let street = Street::new("qu du seujet 36", None, &DataPaths::default()).unwrap();
 /// #     let mat = street_sim::find_matches(&street, Some(Plz::new(1201)), None);
-/// #     assert_eq!(mat.0.unwrap().text, "quai du seujet 36".to_string());
+/// #     assert_eq!(mat.unwrap().candidate.unwrap().text, "quai du seujet 36".to_string());
 /// # }
 /// ```
 ///
 /// ```rust
-/// # use street_sim_rs::{Place, Street, street_sim};
+/// # use street_sim_rs::{DataPaths, Place, Street, street_sim};
 /// #
 /// # fn main() {
-/// #     let street = Street::new("aarstrasse 76", None).unwrap();
-/// #     let mat = street_sim::find_matches(&street, Some(Place::new("Bern")), None);
-/// #     assert_eq!(mat.0.unwrap().text, "aarstrasse 76".to_string());
+/// #     let street = Street::new("aarstrasse 76", None, &DataPaths::default()).unwrap();
+/// #     let mat = street_sim::find_matches(&street, Some(Place::new("Bern", &DataPaths::default()).unwrap()), None);
+/// #     assert_eq!(mat.unwrap().candidate.unwrap().text, "aarstrasse 76".to_string());
 /// # }
 /// ```
 ///
-/// # Panics
+/// # Errors
 ///
-/// Panics if `street` does not contain a number (as each valid street MUST contain an any number)
+/// Never returns an error: the street data is already loaded and parsed by `Street::new`,
+/// and a street without any similar enough candidate is a match with `candidate: None`
 pub fn find_matches<T>(
     street: &Street,
     street_cfg: StreetConfig<T>,
-) -> io::Result<(Option<Candidate>, Option<T>)>
+) -> Result<StreetMatch<T>, Error>
+where
+    T: ToString,
+{
+    match_in_file(
+        &street.address,
+        &street.file,
+        &AddressParser::new(),
+        street_cfg,
+    )
+}
+
+/// The street name and the house number are scored separately:
+/// the street name must be similar enough to pass the sensitivity threshold,
+/// while the house number only affects the ranking of the passed candidates
+#[inline]
+fn cmp_addresses(
+    target: &Address,
+    target_name: &Text,
+    index: usize,
+    candidate: &str,
+    parser: &AddressParser,
+    cfg: &Config,
+) -> Option<Candidate> {
+    let address = parser.parse(candidate);
+    let name = Text::new(address.street_name.to_owned());
+    let (name_similarity, components) = cfg.scores(target_name, &name);
+    if name_similarity - cfg.sens.0 > 0.0 {
+        Some(Candidate {
+            text: candidate.to_string(),
+            // Only the street name is compared as a text
+            cleaned: name.cleaned,
+            similarity: STREET_NAME_WEIGHT * name_similarity
+                + (1.0 - STREET_NAME_WEIGHT) * cmp_house_numbers(target, &address),
+            components,
+            index: Some(index),
+            algo: cfg.similarity.algo(),
+            payload: (),
+        })
+    } else {
+        None
+    }
+}
+
+#[inline]
+fn cmp_house_numbers(target: &Address, candidate: &Address) -> f64 {
+    match (target.house_number, candidate.house_number) {
+        _ if is_same_house_number(target, candidate) => 1.0,
+        (Some(lhs), Some(rhs)) if lhs == rhs => SUFFIX_MISMATCH_SIM,
+        _ => 0.0,
+    }
+}
+
+#[inline]
+fn is_same_house_number(target: &Address, candidate: &Address) -> bool {
+    target.house_number == candidate.house_number && target.suffix == candidate.suffix
+}
+
+#[inline]
+fn best_match(
+    address: &Address,
+    street_candidates: &[String],
+    parser: &AddressParser,
+    cfg: &Config,
+) -> Option<Candidate> {
+    let target_name = Text::new(address.street_name.to_owned());
+    candidate::keep_best(
+        street_candidates
+            .iter()
+            .enumerate()
+            .flat_map(|(index, candidate)| {
+                cmp_addresses(address, &target_name, index, candidate, parser, cfg)
+            }),
+        cfg.num_to_keep,
+    )
+    .ok()
+    .map(|mat| mat[0].clone())
+}
+
+/// The result of matching a street
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreetMatch<T> {
+    pub candidate: Option<Candidate>,
+    /// The location from `StreetConfig` if the candidate was found within it
+    pub location: Option<T>,
+    /// Building or complex name given in parentheses: 'Gerber-Areal', 'Stade de la Maladière'
+    pub building_name: Option<String>,
+}
+
+#[inline]
+fn match_in_file<T>(
+    address: &Address,
+    file: &StreetFile,
+    parser: &AddressParser,
+    street_cfg: StreetConfig<T>,
+) -> Result<StreetMatch<T>, Error>
 where
     T: ToString,
 {
-    let (street_candidates, is_found_in_loc) =
-        street.file.get_streets_by(street_cfg.location.as_ref());
+    let (street_candidates, is_found_in_loc) = file.get_streets_by(street_cfg.location.as_ref());
+    Ok(StreetMatch {
+        candidate: best_match(address, &street_candidates, parser, &street_cfg.cfg),
+        location: if is_found_in_loc {
+            street_cfg.location
+        } else {
+            None
+        },
+        building_name: address.building_name.to_owned(),
+    })
+}
+
+/// The result of matching a single house number from a range or a list
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberMatch {
+    pub address: Address,
+    /// The best candidate for the address, its house number may differ if the address is invalid
+    pub candidate: Option<Candidate>,
+    /// Whether the exact house number (with suffix) exists in the street data
+    pub is_valid: bool,
+}
+
+/// The same as `find_matches`, but matches every house number of a range or a list separately:
+/// 'hohenrainstrasse 10+12+14', 'avenue des baumes 17–19', 'günzenenstrasse 5,5a–c'.
+/// Each house number is flagged whether it exists in the street data or not
+///
+/// # Errors
+///
+/// The matching itself works on the data loaded by `Street::new`, so the error is reserved
+/// for the I/O and parsing failures of the street data
+pub fn find_number_matches<T>(
+    street: &Street,
+    street_cfg: StreetConfig<T>,
+) -> Result<(Vec<NumberMatch>, Option<T>), Error>
+where
+    T: ToString,
+{
+    match_numbers_in_file(
+        &street.addresses,
+        &street.file,
+        &AddressParser::new(),
+        street_cfg,
+    )
+}
+
+#[inline]
+fn match_numbers_in_file<T>(
+    addresses: &[Address],
+    file: &StreetFile,
+    parser: &AddressParser,
+    street_cfg: StreetConfig<T>,
+) -> Result<(Vec<NumberMatch>, Option<T>), Error>
+where
+    T: ToString,
+{
+    let (street_candidates, is_found_in_loc) = file.get_streets_by(street_cfg.location.as_ref());
     Ok((
-        text_sim::cmp_with_arr(&street_candidates, &street.value, &street_cfg.cfg)
-            .ok()
-            .map(|mat| mat[0].clone()),
+        addresses
+            .iter()
+            .map(|address| {
+                let candidate = best_match(address, &street_candidates, parser, &street_cfg.cfg);
+                NumberMatch {
+                    is_valid: candidate.as_ref().is_some_and(|candidate| {
+                        is_same_house_number(address, &parser.parse(&candidate.text))
+                    }),
+                    address: address.to_owned(),
+                    candidate,
+                }
+            })
+            .collect(),
         if is_found_in_loc {
             street_cfg.location
         } else {
@@ -302,19 +689,268 @@ where
     ))
 }
 
+/// Separators between distinct streets: 'löwenstrasse 11/hofplatz', 'avenue des morgines 18, chemin l-hubert 13'
+const STREET_SEPARATORS: &[char] = &['/', ',', ';', '\\'];
+
+/// Splits the input with several streets into the byte spans of separate streets:
+/// 'Avenue de la Gare 3 / Rue du Rhône 2 (Le Kluser)' or
+/// 'Route de Chancy 71 (Lancy Centre) Route de Chancy 77'.
+///
+/// The input is split only if a separator is followed by a word,
+/// so house number lists like '15,17,19', '4/6' or '5,5a–c' stay within one street.
+/// Separators inside parentheses are ignored
+pub fn split_streets(street: &str) -> Vec<Range<usize>> {
+    let starts_with_word = |from: usize| {
+        let mut chars = street[from..].trim_start().chars();
+        matches!((chars.next(), chars.next()), (Some(lhs), Some(rhs)) if lhs.is_alphabetic() && rhs.is_alphabetic())
+    };
+    let mut spans = Vec::new();
+    let (mut start, mut depth) = (0, 0usize);
+    for (idx, ch) in street.char_indices() {
+        let next = idx + ch.len_utf8();
+        match ch {
+            '(' => depth += 1,
+            ')' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 && starts_with_word(next) {
+                    spans.push(start..next);
+                    start = next;
+                }
+            }
+            _ if depth == 0 && STREET_SEPARATORS.contains(&ch) && starts_with_word(next) => {
+                spans.push(start..idx);
+                start = next;
+            }
+            _ => (),
+        }
+    }
+    spans.push(start..street.len());
+    spans
+        .into_iter()
+        .map(|span| trim_span(street, span))
+        .filter(|span| !span.is_empty())
+        .collect()
+}
+
+#[inline]
+fn trim_span(text: &str, span: Range<usize>) -> Range<usize> {
+    let part = &text[span.clone()];
+    let start = span.start + (part.len() - part.trim_start().len());
+    start..(start + part.trim().len())
+}
+
+/// The result of matching a single street from the input with several streets
+#[derive(Debug)]
+pub struct SplitMatch<T> {
+    /// Byte span of the street in the input
+    pub span: Range<usize>,
+    pub text: String,
+    pub result: Result<StreetMatch<T>, Error>,
+}
+
+/// Splits the input with several streets (see `split_streets`)
+/// and runs `find_matches` on every street separately.
+/// The error of one street, e.g. a street without a house number, does not affect the others
+pub fn find_split_matches<T>(
+    street: &str,
+    street_cfg: StreetConfig<T>,
+    paths: &DataPaths,
+) -> Vec<SplitMatch<T>>
+where
+    T: ToString + Clone,
+{
+    split_streets(street)
+        .into_iter()
+        .map(|span| {
+            let text = street[span.clone()].to_string();
+            SplitMatch {
+                result: Street::new(&text, Some(street_cfg.cfg.clone()), paths)
+                    .and_then(|street| find_matches(&street, street_cfg.clone())),
+                span,
+                text,
+            }
+        })
+        .collect()
+}
+
+/// In-memory copy of the whole streets data: the list of official street names,
+/// the table of every street name and the list of places.
+/// The data is read from disk only once in `StreetIndex::load`,
+/// so a single index could be shared across threads and reused for any number of lookups.
+#[derive(Debug)]
+pub struct StreetIndex {
+    street_names: TrigramIndex,
+    street_files: HashMap<String, StreetFile>,
+    places: Vec<String>,
+    parser: AddressParser,
+}
+
+impl StreetIndex {
+    /// # Errors
+    ///
+    /// Returns `Error::MissingData` if the data layout is not complete (see `DataPaths::validate`)
+    /// or an error if any of the data files could not be read or parsed
+    pub fn load(paths: &DataPaths) -> Result<Self, Error> {
+        paths.validate()?;
+        let street_names = read_lines(&paths.street_names)?;
+        let street_files = street_names
+            .iter()
+            .map(|name| Ok((name.to_owned(), StreetFile::new(name, paths)?)))
+            .collect::<Result<HashMap<String, StreetFile>, Error>>()?;
+        Ok(Self {
+            street_names: TrigramIndex::new(street_names),
+            street_files,
+            places: read_lines(&paths.places)?,
+            parser: AddressParser::new(),
+        })
+    }
+
+    /// The same as `Place::new`, but searches within places loaded into the index
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::UnknownLocation` if `value` did not match any place
+    pub fn match_place(&self, value: &str) -> Result<Place, Error> {
+        Place::from_matches(
+            text_sim::cmp_with_arr(
+                &self.places,
+                &Text::new(value.to_string()),
+                &Place::config()?,
+            ),
+            value,
+        )
+    }
+
+    /// Matches `street` as `Street::new` followed by `find_matches` without any disk access,
+    /// but searches all the loaded street names (see `text_sim::cmp_with_index`) instead of
+    /// rescoring the streets most similar by Jaro, so the two could pick different streets
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::DoesNotContainNumbers` if `street` does not contain a number
+    /// and `Error::NotFound` if no official street name is similar enough to `street`
+    pub fn match_street<T>(
+        &self,
+        street: &str,
+        street_cfg: StreetConfig<T>,
+    ) -> Result<StreetMatch<T>, Error>
+    where
+        T: ToString,
+    {
+        let addresses = Street::parse(street, &self.parser)?;
+        match_in_file(
+            &addresses[0],
+            self.find_street_file(&addresses[0], &street_cfg.cfg)?,
+            &self.parser,
+            street_cfg,
+        )
+    }
+
+    /// `match_street` for each of `streets`, spread over the workers of the config executor.
+    /// The results keep the order of `streets` and each one has its own error
+    /// (e.g. `Error::DoesNotContainNumbers`), so a bad item does not abort the batch.
+    /// A street whose worker stopped before its result gets `Error::Aborted`.
+    ///
+    /// Must not be called from a job of the same executor, it waits for the workers
+    pub fn match_batch<T>(
+        self: &Arc<Self>,
+        streets: Vec<String>,
+        street_cfg: StreetConfig<T>,
+    ) -> Vec<Result<StreetMatch<T>, Error>>
+    where
+        T: ToString + Clone + Send + Sync + 'static,
+    {
+        let index = self.clone();
+        let executor = street_cfg.cfg.executor.clone();
+        text_sim::map_in_order(executor.as_ref(), streets, move |street| {
+            index.match_street(street, street_cfg.clone())
+        })
+    }
+
+    /// `find_split_matches` without any disk access, searching the street names
+    /// as `match_street` does
+    pub fn match_split_streets<T>(
+        &self,
+        street: &str,
+        street_cfg: StreetConfig<T>,
+    ) -> Vec<SplitMatch<T>>
+    where
+        T: ToString + Clone,
+    {
+        split_streets(street)
+            .into_iter()
+            .map(|span| {
+                let text = street[span.clone()].to_string();
+                SplitMatch {
+                    result: self.match_street(&text, street_cfg.clone()),
+                    span,
+                    text,
+                }
+            })
+            .collect()
+    }
+
+    /// `Street::new` followed by `find_number_matches` without any disk access,
+    /// searching the street names as `match_street` does
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::DoesNotContainNumbers` if `street` does not contain a number
+    /// and `Error::NotFound` if no official street name is similar enough to `street`
+    pub fn match_street_numbers<T>(
+        &self,
+        street: &str,
+        street_cfg: StreetConfig<T>,
+    ) -> Result<(Vec<NumberMatch>, Option<T>), Error>
+    where
+        T: ToString,
+    {
+        let addresses = Street::parse(street, &self.parser)?;
+        match_numbers_in_file(
+            &addresses,
+            self.find_street_file(&addresses[0], &street_cfg.cfg)?,
+            &self.parser,
+            street_cfg,
+        )
+    }
+
+    #[inline]
+    fn find_street_file(&self, address: &Address, cfg: &Config) -> Result<&StreetFile, Error> {
+        let street_name =
+            &self.find_street_name(&Text::new(address.street_name.to_owned()), cfg)?[0].text;
+        self.street_files.get(street_name).ok_or(Error::NotFound)
+    }
+
+    #[inline]
+    fn find_street_name(&self, street: &Text, cfg: &Config) -> SimResult {
+        text_sim::cmp_with_index(street, &self.street_names, cfg)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::text_sim::{Aggregation, Ensemble};
 
     const STREET_WITHOUT_NUMBERS: &str = "Bernstrasse";
     const STREET_WITH_NUMBER: &str = "Bernstrasse 7";
 
+    /// The candidates are equal only with the same scores, the tests check only the matched text
+    fn matched_text<T>(mat: StreetMatch<T>) -> StreetMatch<T> {
+        StreetMatch {
+            candidate: mat
+                .candidate
+                .map(|candidate| Candidate::from(&candidate.text)),
+            ..mat
+        }
+    }
+
     #[test]
     #[ignore]
     fn max_sensitivity() {
         // Some random string in the input
         let street = "FdsfdsfsdfssFSDfdsfsdfsBernstrasse 7";
-        let street = Street::new(street, None);
+        let street = Street::new(street, None, &DataPaths::default());
         assert!(
             matches!(street, Err(_)),
             "Expected NotFound error, but the value was {:?}",
@@ -333,26 +969,43 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "must contain street number")]
     fn no_numbers_in_street_plz() {
-        find_matches::<Plz>(
-            &Street::new(STREET_WITHOUT_NUMBERS, None).unwrap(),
-            StreetConfig::default(),
-        )
-        .unwrap();
+        assert!(matches!(
+            Street::new(STREET_WITHOUT_NUMBERS, None, &DataPaths::default()),
+            Err(Error::DoesNotContainNumbers(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_street_config_sens() {
+        assert!(matches!(
+            StreetConfig::<Plz>::new(None, 1.5, 1, SimAlgo::default()),
+            Err(Error::InvalidSens(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_place() {
+        assert!(matches!(
+            Place::new("0000000000000000", &DataPaths::default()),
+            Err(Error::UnknownLocation(_))
+        ));
     }
 
     fn assert_clean_street(expected_street: &str, street_to_clean: &str) {
         assert_eq!(
             expected_street.to_string(),
-            Street::new(street_to_clean, None).unwrap().value.cleaned
+            Street::new(street_to_clean, None, &DataPaths::default())
+                .unwrap()
+                .value
+                .cleaned
         );
     }
 
     #[test]
     fn clean_street() {
         assert_clean_street("bernstrasse7", "   Bernstrasse 7   ");
-        assert_clean_street("bernstrassea4", "   a4 Bernstrasse   ");
+        assert_clean_street("bernstrasse4a", "   a4 Bernstrasse   ");
         assert_clean_street("bernstrasse4", "   4 Bernstrasse   ");
         assert_clean_street("bernstrasse4a", "   Bernstrasse 4a, 5, 6   ");
         assert_clean_street("bernstrasse4a", "   Bernstrasse 4a 5 6   ");
@@ -363,18 +1016,208 @@ mod tests {
     }
 
     #[test]
-    fn match_with_place() {
-        let location = Place::new("bercher");
-        assert_eq!(
-            find_matches(
-                &Street::new("ch de saint-cierges 3", None).unwrap(),
-                StreetConfig::default_with(location.to_owned()),
+    fn match_house_number_separately() {
+        let file = StreetFile {
+            values: toml::from_str(
+                r#"1201 = ["quai du seujet 34", "quai du seujet 36a", "quai du seujet 36"]"#,
             )
             .unwrap(),
-            (
-                Some(Candidate::from("chemin de saint-cierges 3")),
-                Some(location)
+        };
+        let parser = AddressParser::new();
+        let mat = match_in_file(
+            &parser.parse("qu du seujet 36"),
+            &file,
+            &parser,
+            StreetConfig::default_with(Plz::new(1201)),
+        )
+        .unwrap();
+        assert_eq!(
+            mat.candidate.map(|c| c.text).as_deref(),
+            Some("quai du seujet 36")
+        );
+        let mat = match_in_file(
+            &parser.parse("qu du seujet 36 A"),
+            &file,
+            &parser,
+            StreetConfig::default_with(Plz::new(1201)),
+        )
+        .unwrap();
+        assert_eq!(
+            mat.candidate.map(|c| c.text).as_deref(),
+            Some("quai du seujet 36a")
+        );
+    }
+
+    #[test]
+    fn match_with_building_name() {
+        let file = StreetFile {
+            values: toml::from_str(r#"8001 = ["löwenstrasse 11", "löwenstrasse 13"]"#).unwrap(),
+        };
+        let parser = AddressParser::new();
+        let mat = match_in_file(
+            &parser.parse("Löwenstr. 11 (Gerber-Areal)"),
+            &file,
+            &parser,
+            StreetConfig::default_with(Plz::new(8001)),
+        )
+        .unwrap();
+        assert_eq!(
+            matched_text(mat),
+            StreetMatch {
+                candidate: Some(Candidate::from("löwenstrasse 11")),
+                location: Some(Plz::new(8001)),
+                building_name: Some("Gerber-Areal".to_string()),
+            }
+        );
+        assert!(matches!(
+            Street::parse("Allée du Communet (Métropole 2000)", &parser),
+            Err(Error::DoesNotContainNumbers(_))
+        ));
+    }
+
+    #[test]
+    fn match_house_number_list() {
+        let file = StreetFile {
+            values: toml::from_str(
+                r#"lausanne = ["rue des terreaux 15", "rue des terreaux 17", "rue des terreaux 19"]"#,
             )
+            .unwrap(),
+        };
+        let parser = AddressParser::new();
+        let (matches, location) = match_numbers_in_file(
+            &parser.parse_all("Rue des Terreaux 15–21"),
+            &file,
+            &parser,
+            StreetConfig::default_with(Place("lausanne".to_string())),
+        )
+        .unwrap();
+        assert_eq!(location, Some(Place("lausanne".to_string())));
+        assert_eq!(
+            matches
+                .iter()
+                .map(|mat| (mat.address.house_number.unwrap(), mat.is_valid))
+                .collect::<Vec<(u32, bool)>>(),
+            vec![(15, true), (17, true), (19, true), (21, false)]
+        );
+        assert_eq!(
+            matches[1].candidate.as_ref().map(|c| c.text.as_str()),
+            Some("rue des terreaux 17")
+        );
+    }
+
+    fn assert_split(street: &str, expected: &[&str]) {
+        assert_eq!(
+            split_streets(street)
+                .into_iter()
+                .map(|span| &street[span])
+                .collect::<Vec<&str>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn split_multi_streets() {
+        assert_split("Löwenstr. 11/Hofplatz", &["Löwenstr. 11", "Hofplatz"]);
+        assert_split(
+            "Avenue de la Gare 3 / Rue du Rhône 2 (Le Kluser)",
+            &["Avenue de la Gare 3", "Rue du Rhône 2 (Le Kluser)"],
+        );
+        assert_split(
+            "Avenue des Morgines 18, Chemin L-Hubert 13",
+            &["Avenue des Morgines 18", "Chemin L-Hubert 13"],
+        );
+        assert_split(
+            "Route de Chancy 71 (Lancy Centre) Route de Chancy 77",
+            &["Route de Chancy 71 (Lancy Centre)", "Route de Chancy 77"],
+        );
+        assert_split(
+            "Wright-Strasse 37,39,41,43 (Wilhelm & Bertha, Glattpark)",
+            &["Wright-Strasse 37,39,41,43 (Wilhelm & Bertha, Glattpark)"],
+        );
+    }
+
+    #[test]
+    fn split_keeps_house_number_lists() {
+        assert_split("Günzenenstrasse 5,5a–c", &["Günzenenstrasse 5,5a–c"]);
+        assert_split("Pierre-à-Mazel 4/6", &["Pierre-à-Mazel 4/6"]);
+        assert_split("Mühlematt 7\\11", &["Mühlematt 7\\11"]);
+        assert_split("Bernstrasse 4a, b", &["Bernstrasse 4a, b"]);
+    }
+
+    #[test]
+    fn match_split_streets() {
+        let street = "Avenue de la Gare 3 / Rue du Rhône 2";
+        let matches = find_split_matches(
+            street,
+            StreetConfig::<Plz>::default(),
+            &DataPaths::default(),
+        );
+        assert_eq!(matches.len(), 2);
+        assert_eq!(&street[matches[1].span.clone()], "Rue du Rhône 2");
+        assert_eq!(
+            matches[0]
+                .result
+                .as_ref()
+                .unwrap()
+                .candidate
+                .as_ref()
+                .map(|c| c.text.as_str()),
+            Some("avenue de la gare 3")
+        );
+        assert_eq!(
+            matches[1]
+                .result
+                .as_ref()
+                .unwrap()
+                .candidate
+                .as_ref()
+                .map(|c| c.text.as_str()),
+            Some("rue du rhône 2")
+        );
+    }
+
+    #[test]
+    fn match_with_place() {
+        let location = Place::new("bercher", &DataPaths::default()).unwrap();
+        assert_eq!(
+            matched_text(
+                find_matches(
+                    &Street::new("ch de saint-cierges 3", None, &DataPaths::default()).unwrap(),
+                    StreetConfig::default_with(location.to_owned()),
+                )
+                .unwrap()
+            ),
+            StreetMatch {
+                candidate: Some(Candidate::from("chemin de saint-cierges 3")),
+                location: Some(location),
+                building_name: None,
+            }
+        );
+    }
+
+    #[test]
+    fn match_with_ensemble() {
+        let location = Place::new("bercher", &DataPaths::default()).unwrap();
+        let ensemble = Ensemble::of(
+            &[SimAlgo::JaroWinkler, SimAlgo::Levenshtein],
+            Aggregation::Max,
+        )
+        .unwrap();
+        let candidate = find_matches(
+            &Street::new("ch de saint-cierges 3", None, &DataPaths::default()).unwrap(),
+            StreetConfig::new(Some(location), 0.7, 1, ensemble).unwrap(),
+        )
+        .unwrap()
+        .candidate
+        .unwrap();
+        assert_eq!(candidate.text, "chemin de saint-cierges 3");
+        assert_eq!(
+            candidate
+                .components
+                .iter()
+                .map(|(algo, _)| *algo)
+                .collect::<Vec<SimAlgo>>(),
+            vec![SimAlgo::JaroWinkler, SimAlgo::Levenshtein]
         );
     }
 
@@ -382,12 +1225,16 @@ mod tests {
     #[ignore]
     fn match_without_place() {
         let mat = find_matches::<Place>(
-            &Street::new("ch de saint-cierges 3", None).unwrap(),
+            &Street::new("ch de saint-cierges 3", None, &DataPaths::default()).unwrap(),
             StreetConfig::default(),
         );
         assert_eq!(
-            mat.unwrap(),
-            (Some(Candidate::from("chemin de saint-cierges 3")), None)
+            matched_text(mat.unwrap()),
+            StreetMatch {
+                candidate: Some(Candidate::from("chemin de saint-cierges 3")),
+                location: None,
+                building_name: None,
+            }
         );
     }
 
@@ -395,12 +1242,16 @@ mod tests {
     fn match_with_plz() {
         let location = Plz::new(1201);
         let mat = find_matches(
-            &Street::new("qu du seujet 36", None).unwrap(),
+            &Street::new("qu du seujet 36", None, &DataPaths::default()).unwrap(),
             StreetConfig::default_with(location.to_owned()),
         );
         assert_eq!(
-            mat.unwrap(),
-            (Some(Candidate::from("quai du seujet 36")), Some(location))
+            matched_text(mat.unwrap()),
+            StreetMatch {
+                candidate: Some(Candidate::from("quai du seujet 36")),
+                location: Some(location),
+                building_name: None,
+            }
         );
     }
 
@@ -408,12 +1259,16 @@ mod tests {
     #[ignore]
     fn match_without_plz() {
         let mat = find_matches::<Plz>(
-            &Street::new("qu du seujet 36", None).unwrap(),
+            &Street::new("qu du seujet 36", None, &DataPaths::default()).unwrap(),
             StreetConfig::default(),
         );
         assert_eq!(
-            mat.unwrap(),
-            (Some(Candidate::from("quai du seujet 36")), None)
+            matched_text(mat.unwrap()),
+            StreetMatch {
+                candidate: Some(Candidate::from("quai du seujet 36")),
+                location: None,
+                building_name: None,
+            }
         );
     }
 
@@ -421,12 +1276,16 @@ mod tests {
     #[ignore]
     fn match_with_wrong_plz() {
         let mat = find_matches(
-            &Street::new("qu du seujet 36", None).unwrap(),
+            &Street::new("qu du seujet 36", None, &DataPaths::default()).unwrap(),
             StreetConfig::default_with(Plz::new(1231231)),
         );
         assert_eq!(
-            mat.unwrap(),
-            (Some(Candidate::from("quai du seujet 36")), None)
+            matched_text(mat.unwrap()),
+            StreetMatch {
+                candidate: Some(Candidate::from("quai du seujet 36")),
+                location: None,
+                building_name: None,
+            }
         );
     }
 
@@ -435,12 +1294,16 @@ mod tests {
     fn match_with_wrong_first_word() {
         let location = Plz::new(1201);
         let mat = find_matches(
-            &Street::new("uai du seujet 36", None).unwrap(),
+            &Street::new("uai du seujet 36", None, &DataPaths::default()).unwrap(),
             StreetConfig::default_with(location.to_owned()),
         );
         assert_eq!(
-            mat.unwrap(),
-            (Some(Candidate::from("quai du seujet 36")), Some(location))
+            matched_text(mat.unwrap()),
+            StreetMatch {
+                candidate: Some(Candidate::from("quai du seujet 36")),
+                location: Some(location),
+                building_name: None,
+            }
         );
     }
 
@@ -448,12 +1311,16 @@ mod tests {
     #[ignore]
     fn match_with_wrong_first_word_no_plz() {
         let mat = find_matches::<Plz>(
-            &Street::new("uai du seujet 36", None).unwrap(),
+            &Street::new("uai du seujet 36", None, &DataPaths::default()).unwrap(),
             StreetConfig::default(),
         );
         assert_eq!(
-            mat.unwrap(),
-            (Some(Candidate::from("quai du seujet 36")), None)
+            matched_text(mat.unwrap()),
+            StreetMatch {
+                candidate: Some(Candidate::from("quai du seujet 36")),
+                location: None,
+                building_name: None,
+            }
         );
     }
 
@@ -462,38 +1329,169 @@ mod tests {
     fn match_with_wrong_first_word_wrong_plz() {
         let location = Plz::new(2132131);
         let mat = find_matches(
-            &Street::new("uai du seujet 36", None).unwrap(),
+            &Street::new("uai du seujet 36", None, &DataPaths::default()).unwrap(),
             StreetConfig::default_with(location),
         );
         assert_eq!(
-            mat.unwrap(),
-            (Some(Candidate::from("quai du seujet 36")), None)
+            matched_text(mat.unwrap()),
+            StreetMatch {
+                candidate: Some(Candidate::from("quai du seujet 36")),
+                location: None,
+                building_name: None,
+            }
         );
     }
 
     #[test]
     fn match_wil_place() {
-        let location = Place::new("Wil SG");
+        let location = Place::new("Wil SG", &DataPaths::default()).unwrap();
         let mat = find_matches(
-            &Street::new("Zürcherstrasse 3", None).unwrap(),
+            &Street::new("Zürcherstrasse 3", None, &DataPaths::default()).unwrap(),
             StreetConfig::default_with(location),
         );
         assert_eq!(
-            mat.unwrap(),
-            (Some(Candidate::from("zürcherstrasse 3")), None)
+            matched_text(mat.unwrap()),
+            StreetMatch {
+                candidate: Some(Candidate::from("zürcherstrasse 3")),
+                location: None,
+                building_name: None,
+            }
         );
     }
 
+    #[test]
+    fn data_paths_layout() {
+        let paths = DataPaths::new("/data");
+        assert_eq!(paths.places, PathBuf::from("/data/places.txt"));
+        assert_eq!(
+            paths.street_names,
+            PathBuf::from("/data/streets_data/street_names.txt")
+        );
+        assert_eq!(paths.streets_data, PathBuf::from("/data/streets_data"));
+        assert_eq!(
+            paths.street_file("rue a/b"),
+            PathBuf::from("/data/streets_data/rue a%2Cb.toml")
+        );
+    }
+
+    #[test]
+    fn data_paths_from_env() {
+        env::set_var(DATA_ROOT_ENV, "/data");
+        assert_eq!(DataPaths::from_env().unwrap(), DataPaths::new("/data"));
+        env::remove_var(DATA_ROOT_ENV);
+        assert!(matches!(
+            DataPaths::from_env(),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn data_paths_from_config() {
+        let dir = env::temp_dir().join(format!(
+            "mass_data_paths_from_config_{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("mass.toml");
+        fs::write(&config, "root = \"data\"\nplaces = \"/places.txt\"\n").unwrap();
+        assert_eq!(
+            DataPaths::from_config(&config).unwrap(),
+            DataPaths {
+                places: PathBuf::from("/places.txt"),
+                ..DataPaths::new(dir.join("data"))
+            }
+        );
+        fs::write(&config, "places = \"/places.txt\"\n").unwrap();
+        assert!(matches!(
+            DataPaths::from_config(&config),
+            Err(Error::InvalidConfig(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn validate_data_paths() {
+        assert!(DataPaths::default().validate().is_ok());
+        assert!(matches!(
+            DataPaths::new("./does_not_exist").validate(),
+            Err(Error::MissingData(_))
+        ));
+        assert!(matches!(
+            StreetIndex::load(&DataPaths::new("./does_not_exist")),
+            Err(Error::MissingData(_))
+        ));
+    }
+
+    #[test]
+    fn index_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<StreetIndex>();
+    }
+
+    #[test]
+    fn match_with_index() {
+        let index = StreetIndex::load(&DataPaths::default()).unwrap();
+        let location = index.match_place("bercher").unwrap();
+        assert_eq!(
+            location,
+            Place::new("bercher", &DataPaths::default()).unwrap()
+        );
+        assert_eq!(
+            matched_text(
+                index
+                    .match_street(
+                        "ch de saint-cierges 3",
+                        StreetConfig::default_with(location.to_owned())
+                    )
+                    .unwrap()
+            ),
+            StreetMatch {
+                candidate: Some(Candidate::from("chemin de saint-cierges 3")),
+                location: Some(location),
+                building_name: None,
+            }
+        );
+    }
+
+    #[test]
+    fn match_batch_keeps_order() {
+        let index = Arc::new(StreetIndex::load(&DataPaths::default()).unwrap());
+        let location = index.match_place("bercher").unwrap();
+        let streets = vec![
+            "ch de saint-cierges 3".to_string(),
+            STREET_WITHOUT_NUMBERS.to_string(),
+            "chemin de saint-cierges 3".to_string(),
+        ];
+        let matches = index.match_batch(
+            streets.clone(),
+            StreetConfig::default_with(location.clone()),
+        );
+        assert_eq!(streets.len(), matches.len());
+        assert!(matches!(matches[1], Err(Error::DoesNotContainNumbers(_))));
+        for idx in [0, 2] {
+            assert_eq!(
+                matches[idx].as_ref().unwrap(),
+                &index
+                    .match_street(&streets[idx], StreetConfig::default_with(location.clone()))
+                    .unwrap()
+            );
+        }
+    }
+
     #[test]
     fn match_pfaffikon_place() {
-        let location = Place::new("Pfäffikon");
+        let location = Place::new("Pfäffikon", &DataPaths::default()).unwrap();
         let mat = find_matches(
-            &Street::new("Rigistrasse 10", None).unwrap(),
+            &Street::new("Rigistrasse 10", None, &DataPaths::default()).unwrap(),
             StreetConfig::default_with(location.clone()),
         );
         assert_eq!(
-            mat.unwrap(),
-            (Some(Candidate::from("rigistrasse 10")), None)
+            matched_text(mat.unwrap()),
+            StreetMatch {
+                candidate: Some(Candidate::from("rigistrasse 10")),
+                location: None,
+                building_name: None,
+            }
         );
         assert_eq!(location.to_string(), String::from("Pfäffikon ZG"));
     }
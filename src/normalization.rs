@@ -0,0 +1,131 @@
+//! This module normalises the unicode representation of a text before the comparison,
+//! so visually equal texts like composed and decomposed 'ü' are equal for the matcher as well
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+const APOSTROPHES: &[char] = &['’', '‘', 'ʼ', '`', '´', '′'];
+const DASHES: &[char] = &['‐', '‑', '‒', '–', '—', '―', '−'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnicodeForm {
+    None,
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Normalization {
+    pub form: UnicodeForm,
+    /// Removes the diacritics: 'genéral' -> 'general', 'zürich' -> 'zurich'
+    pub fold_accents: bool,
+    /// Replaces 'ß' with 'ss'
+    pub sharp_s: bool,
+    /// Replaces typographic apostrophes with '\''
+    pub apostrophes: bool,
+    /// Replaces typographic dashes with '-'
+    pub dashes: bool,
+    /// Trims the text and collapses any whitespace sequence into a single space
+    pub whitespace: bool,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            form: UnicodeForm::Nfc,
+            fold_accents: false,
+            sharp_s: true,
+            apostrophes: true,
+            dashes: true,
+            whitespace: true,
+        }
+    }
+}
+
+impl Normalization {
+    /// Leaves the text as is
+    pub fn none() -> Self {
+        Self {
+            form: UnicodeForm::None,
+            fold_accents: false,
+            sharp_s: false,
+            apostrophes: false,
+            dashes: false,
+            whitespace: false,
+        }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = if self.fold_accents {
+            text.nfd().filter(|ch| !is_combining_mark(*ch)).collect()
+        } else {
+            text.to_string()
+        };
+        text = match self.form {
+            UnicodeForm::None => text,
+            UnicodeForm::Nfc => text.nfc().collect(),
+            UnicodeForm::Nfd => text.nfd().collect(),
+            UnicodeForm::Nfkc => text.nfkc().collect(),
+            UnicodeForm::Nfkd => text.nfkd().collect(),
+        };
+        if self.sharp_s {
+            text = text.replace(['ß', 'ẞ'], "ss");
+        }
+        if self.apostrophes {
+            text = text.replace(APOSTROPHES, "'");
+        }
+        if self.dashes {
+            text = text.replace(DASHES, "-");
+        }
+        if self.whitespace {
+            text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose_umlauts() {
+        assert_eq!(
+            Normalization::default().apply("Zu\u{308}rich"),
+            Normalization::default().apply("Zürich")
+        );
+        assert_eq!(
+            Normalization::none().apply("Zu\u{308}rich"),
+            "Zu\u{308}rich"
+        );
+    }
+
+    #[test]
+    fn fold_accents() {
+        let normalization = Normalization {
+            fold_accents: true,
+            ..Normalization::default()
+        };
+        assert_eq!(normalization.apply("Genéral"), "General");
+        assert_eq!(normalization.apply("Zu\u{308}rich"), "Zurich");
+        assert_eq!(Normalization::default().apply("Genéral"), "Genéral");
+    }
+
+    #[test]
+    fn unify_characters() {
+        let normalization = Normalization::default();
+        assert_eq!(
+            normalization.apply("Grossstrasse"),
+            normalization.apply("Großstrasse")
+        );
+        assert_eq!(normalization.apply("Rue de l’Arc"), "Rue de l'Arc");
+        assert_eq!(
+            normalization.apply("Avenue des Baumes 17–19"),
+            "Avenue des Baumes 17-19"
+        );
+        assert_eq!(
+            normalization.apply("  Rue\u{a0}du \t Clos  "),
+            "Rue du Clos"
+        );
+    }
+}
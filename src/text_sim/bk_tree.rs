@@ -0,0 +1,288 @@
+//! BK-tree over the candidates for the edit-distance algorithms: the triangle inequality
+//! lets a query skip every subtree farther than the searched radius
+use super::{SimAlgo, SimFunc};
+use crate::{
+    candidate::{self, Candidate, Sens, SimResult, Text},
+    mass::Error,
+};
+use std::{
+    fs::File,
+    io::{self, prelude::*, BufReader},
+    path::Path,
+};
+
+#[derive(Debug, Clone)]
+struct Node {
+    id: usize,
+    children: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BkTreeIndex {
+    algo: SimAlgo,
+    texts: Vec<Text>,
+    nodes: Vec<Node>,
+}
+
+impl BkTreeIndex {
+    /// `algo` must be `SimAlgo::Levenshtein`, `SimAlgo::DamerauLevenshtein` or `SimAlgo::Osa`,
+    /// the candidates are cleaned by `Text::new`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidConfig` if `algo` is not an edit distance
+    pub fn new(candidates: &[String], algo: SimAlgo) -> Result<Self, Error> {
+        if !matches!(
+            algo,
+            SimAlgo::Levenshtein | SimAlgo::DamerauLevenshtein | SimAlgo::Osa
+        ) {
+            return Err(Error::InvalidConfig(format!(
+                "BK-tree does not support {:?}",
+                algo
+            )));
+        }
+        let mut index = Self {
+            algo,
+            texts: candidates
+                .iter()
+                .map(|candidate| Text::new(candidate.to_owned()))
+                .collect(),
+            nodes: Vec::with_capacity(candidates.len()),
+        };
+        for id in 0..index.texts.len() {
+            index.insert(id);
+        }
+        Ok(index)
+    }
+
+    /// Builds the index from a file with a candidate on each line
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file could not be read
+    /// and `Error::InvalidConfig` if `algo` is not an edit distance
+    pub fn from_file(file: &Path, algo: SimAlgo) -> Result<Self, Error> {
+        Self::new(
+            &BufReader::new(File::open(file)?)
+                .lines()
+                .collect::<io::Result<Vec<String>>>()?,
+            algo,
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.texts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty()
+    }
+
+    /// The distance the tree is built on. OSA is not a metric,
+    /// so its tree is built on Damerau-Levenshtein which is never larger
+    #[inline]
+    fn metric(&self, lhs: &str, rhs: &str) -> usize {
+        match self.algo {
+            SimAlgo::Levenshtein => strsim::levenshtein(lhs, rhs),
+            _ => strsim::damerau_levenshtein(lhs, rhs),
+        }
+    }
+
+    #[inline]
+    fn distance(&self, lhs: &str, rhs: &str) -> usize {
+        match self.algo {
+            SimAlgo::Osa => strsim::osa_distance(lhs, rhs),
+            _ => self.metric(lhs, rhs),
+        }
+    }
+
+    fn insert(&mut self, id: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push(Node {
+                id,
+                children: Vec::new(),
+            });
+            return;
+        }
+        let mut node = 0;
+        loop {
+            let distance = self.metric(
+                &self.texts[self.nodes[node].id].cleaned,
+                &self.texts[id].cleaned,
+            );
+            match self.nodes[node]
+                .children
+                .iter()
+                .find(|(child_distance, _)| *child_distance == distance)
+            {
+                Some((_, child)) => node = *child,
+                None => {
+                    self.nodes.push(Node {
+                        id,
+                        children: Vec::new(),
+                    });
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.push((distance, child));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// The ids of the candidates within `radius` of the tree metric in their original order
+    fn within(&self, target: &str, radius: usize) -> Vec<usize> {
+        let mut ids = Vec::new();
+        let mut stack = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let distance = self.metric(target, &self.texts[node.id].cleaned);
+            if distance <= radius {
+                ids.push(node.id);
+            }
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|(child_distance, _)| child_distance.abs_diff(distance) <= radius)
+                    .map(|(_, child)| *child),
+            );
+        }
+        ids.sort_unstable();
+        ids
+    }
+
+    #[inline]
+    fn candidates(
+        &self,
+        ids: Vec<usize>,
+        target: &str,
+        keep: impl Fn(f64) -> bool,
+    ) -> Vec<Candidate> {
+        let sim_func: SimFunc = self.algo.into();
+        ids.into_iter()
+            .flat_map(|id| {
+                let similarity = sim_func(target, &self.texts[id].cleaned);
+                keep(similarity).then(|| Candidate {
+                    text: self.texts[id].init.to_owned(),
+                    cleaned: self.texts[id].cleaned.to_owned(),
+                    similarity,
+                    index: Some(id),
+                    algo: Some(self.algo),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    /// Every candidate within `max_distance` edits of `text`, the most similar first
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if there is no such candidate
+    pub fn radius(&self, text: &Text, max_distance: usize) -> SimResult {
+        let ids = self
+            .within(&text.cleaned, max_distance)
+            .into_iter()
+            .filter(|id| self.distance(&text.cleaned, &self.texts[*id].cleaned) <= max_distance)
+            .collect();
+        let candidates = self.candidates(ids, &text.cleaned, |_| true);
+        let len = candidates.len();
+        candidate::keep_best(candidates, len)
+    }
+
+    /// The same as `cmp_with_arr` with `algo` of the index, but only the candidates
+    /// close enough to be more similar than `sens` are compared
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if no candidate is more similar than `sens`
+    pub fn top_k(&self, text: &Text, sens: Sens, num_to_keep: usize) -> SimResult {
+        // The similarity of a candidate `d` edits away is at most `1 - d / (len + d)`,
+        // so it is more similar than `sens` only if `d < (1 - sens) * len / sens`
+        let len = text.cleaned.chars().count();
+        let radius = ((1.0 - sens.0) * len as f64 / sens.0).floor() as usize;
+        let candidates = self.candidates(
+            self.within(&text.cleaned, radius),
+            &text.cleaned,
+            |similarity| similarity - sens.0 > 0.0,
+        );
+        candidate::keep_best(candidates, num_to_keep)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_sim::{cmp_with_arr, Config};
+
+    const DATA_FILE: &str = "./test_data/streets_data/street_names.txt";
+
+    #[test]
+    fn same_as_linear_scan() {
+        let names = BufReader::new(File::open(DATA_FILE).unwrap())
+            .lines()
+            .collect::<io::Result<Vec<String>>>()
+            .unwrap();
+        for algo in [
+            SimAlgo::Levenshtein,
+            SimAlgo::DamerauLevenshtein,
+            SimAlgo::Osa,
+        ] {
+            let index = BkTreeIndex::new(&names, algo).unwrap();
+            for sens in [0.5, 0.7, 0.9] {
+                let cfg = Config::new(Sens(sens), 10, algo, None);
+                for target in [
+                    "qu du seujet",
+                    "bernstrase",
+                    "ch de saint-cierges",
+                    "uai du seujte",
+                ] {
+                    let text = Text::new(target.to_string());
+                    let expected = cmp_with_arr(&names, &text, &cfg);
+                    let actual = index.top_k(&text, Sens(sens), 10);
+                    match (expected, actual) {
+                        (Ok(expected), Ok(actual)) => {
+                            assert_eq!(expected, actual);
+                            assert_eq!(
+                                expected.iter().map(|c| c.similarity).collect::<Vec<f64>>(),
+                                actual.iter().map(|c| c.similarity).collect::<Vec<f64>>()
+                            );
+                        }
+                        (Err(Error::NotFound), Err(Error::NotFound)) => {}
+                        (expected, actual) => panic!("{:?} != {:?}", expected, actual),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn find_within_radius() {
+        let index = BkTreeIndex::from_file(Path::new(DATA_FILE), SimAlgo::Osa).unwrap();
+        let matches = index
+            .radius(&Text::new("quai du seujte".to_string()), 1)
+            .unwrap();
+        assert_eq!(
+            matches
+                .iter()
+                .map(|c| c.text.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["quai du seujet"]
+        );
+        assert!(matches!(
+            index.radius(&Text::new("xyz".to_string()), 0),
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[test]
+    fn only_edit_distances() {
+        assert!(matches!(
+            BkTreeIndex::new(&[], SimAlgo::Jaro),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+//! Long-lived workers of the parallel comparisons: creating threads for every lookup
+//! costs more than the matching itself, so the pool is created once and shared by the configs
+use crate::mass::Error;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex, OnceLock,
+    },
+    thread,
+};
+use threadpool::ThreadPool;
+
+/// A job run by an `Executor`
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Runs the jobs of the parallel comparisons, e.g. a `MatchEngine` or a wrapper
+/// around an existing pool of the application
+pub trait Executor: Send + Sync {
+    /// Runs `job` on one of the workers without waiting for it
+    fn execute(&self, job: Job);
+
+    /// The number of jobs which could run at once, a comparison is split into as many jobs
+    fn num_of_threads(&self) -> usize;
+}
+
+/// Pool of threads created once and shared by every `Config` cloned from the same one
+pub struct MatchEngine {
+    pool: Mutex<ThreadPool>,
+    num_of_threads: usize,
+}
+
+impl MatchEngine {
+    /// # Panics
+    ///
+    /// Panics if `num_of_threads` is 0
+    pub fn new(num_of_threads: usize) -> Self {
+        Self {
+            pool: Mutex::new(ThreadPool::new(num_of_threads)),
+            num_of_threads,
+        }
+    }
+
+    /// The engine sized by the available parallelism, created at the first call
+    pub fn shared() -> Arc<MatchEngine> {
+        static ENGINE: OnceLock<Arc<MatchEngine>> = OnceLock::new();
+        ENGINE
+            .get_or_init(|| {
+                Arc::new(MatchEngine::new(
+                    thread::available_parallelism().map_or(1, |n| n.get()),
+                ))
+            })
+            .clone()
+    }
+
+    /// The engine of `num_of_threads` threads shared by every caller asking for the same size,
+    /// created at the first call
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_of_threads` is 0
+    pub fn with_threads(num_of_threads: usize) -> Arc<MatchEngine> {
+        static ENGINES: OnceLock<Mutex<HashMap<usize, Arc<MatchEngine>>>> = OnceLock::new();
+        // Checked before the lock, so a panic does not poison the engines of the other sizes
+        assert!(num_of_threads > 0, "an engine needs at least one thread");
+        ENGINES
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap()
+            .entry(num_of_threads)
+            .or_insert_with(|| Arc::new(MatchEngine::new(num_of_threads)))
+            .clone()
+    }
+}
+
+impl Executor for MatchEngine {
+    #[inline]
+    fn execute(&self, job: Job) {
+        self.pool.lock().unwrap().execute(job);
+    }
+
+    #[inline]
+    fn num_of_threads(&self) -> usize {
+        self.num_of_threads
+    }
+}
+
+/// The comparison would wait forever for the jobs of an executor without any thread
+pub(crate) fn no_threads() -> Error {
+    Error::InvalidConfig("the executor has no threads".to_string())
+}
+
+/// Applies `func` to every item on the workers of `executor`, the results keep the order
+/// of `items`. Must not be called from a job of the same `executor`: it waits for the workers.
+///
+/// An item gets `Error::Aborted` if its worker stopped before handing back the result
+pub(crate) fn map_in_order<T, R, F>(
+    executor: &dyn Executor,
+    items: Vec<T>,
+    func: F,
+) -> Vec<Result<R, Error>>
+where
+    T: Send + Sync + 'static,
+    R: Send + 'static,
+    F: Fn(&T) -> Result<R, Error> + Send + Sync + 'static,
+{
+    let num_of_items = items.len();
+    if executor.num_of_threads() == 0 {
+        return (0..num_of_items).map(|_| Err(no_threads())).collect();
+    }
+    let items = Arc::new(items);
+    let func = Arc::new(func);
+    // The workers take the next item when they are done, so a slow item does not hold up a chunk
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..executor.num_of_threads().min(num_of_items) {
+        let items = items.clone();
+        let func = func.clone();
+        let next = next.clone();
+        let sender = sender.clone();
+        executor.execute(Box::new(move || loop {
+            let idx = next.fetch_add(1, Ordering::Relaxed);
+            let Some(item) = items.get(idx) else {
+                break;
+            };
+            // The receiver is dropped only if the caller has panicked
+            let _ = sender.send((idx, func(item)));
+        }));
+    }
+    drop(sender);
+    let mut results = (0..num_of_items)
+        .map(|_| None)
+        .collect::<Vec<Option<Result<R, Error>>>>();
+    // The iteration ends as soon as every worker is gone, so a lost item is not waited for
+    for (idx, result) in receiver.iter().take(num_of_items) {
+        results[idx] = Some(result);
+    }
+    results
+        .into_iter()
+        .map(|result| result.unwrap_or(Err(Error::Aborted)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn runs_all_jobs() {
+        let engine = MatchEngine::new(3);
+        let (sender, receiver) = mpsc::channel();
+        for job in 0..10 {
+            let sender = sender.clone();
+            engine.execute(Box::new(move || sender.send(job).unwrap()));
+        }
+        drop(sender);
+        let mut done = receiver.iter().collect::<Vec<usize>>();
+        done.sort_unstable();
+        assert_eq!((0..10).collect::<Vec<usize>>(), done);
+    }
+
+    #[test]
+    fn maps_in_order() {
+        let engine = MatchEngine::new(4);
+        let squares = map_in_order(&engine, (0..100).collect(), |n: &usize| Ok(n * n));
+        assert_eq!(
+            (0..100).map(|n| n * n).collect::<Vec<usize>>(),
+            squares
+                .into_iter()
+                .map(Result::unwrap)
+                .collect::<Vec<usize>>()
+        );
+        assert!(map_in_order(&engine, Vec::new(), |n: &usize| Ok(*n)).is_empty());
+    }
+
+    #[test]
+    fn lost_items_are_aborted() {
+        let engine = MatchEngine::new(2);
+        let results = map_in_order(&engine, (0..10).collect(), |n: &usize| {
+            assert_ne!(*n, 3, "the worker panics");
+            Ok(*n)
+        });
+        assert!(matches!(results[3], Err(Error::Aborted)));
+        assert_eq!(9, results.iter().filter(|result| result.is_ok()).count());
+
+        struct NoThreads;
+
+        impl Executor for NoThreads {
+            fn execute(&self, _job: Job) {}
+
+            fn num_of_threads(&self) -> usize {
+                0
+            }
+        }
+
+        let results = map_in_order(&NoThreads, vec![1], |n: &usize| Ok(*n));
+        assert!(matches!(results[0], Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn shared_engine_is_created_once() {
+        assert!(Arc::ptr_eq(&MatchEngine::shared(), &MatchEngine::shared()));
+        assert!(Arc::ptr_eq(
+            &MatchEngine::with_threads(2),
+            &MatchEngine::with_threads(2)
+        ));
+        assert_eq!(3, MatchEngine::with_threads(3).num_of_threads());
+    }
+}
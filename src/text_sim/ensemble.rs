@@ -0,0 +1,169 @@
+//! The scorer combining several `SimAlgo` into a single similarity:
+//! e.g. Jaro-Winkler handles the typos in the prefix and Levenshtein handles the truncation
+use super::{SimAlgo, Similarity};
+use crate::{candidate::Text, mass::Error};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Aggregation {
+    /// Weighted mean of the scores
+    #[default]
+    Weighted,
+    /// The best score, the weights are ignored
+    Max,
+    /// The worst score, the weights are ignored
+    Min,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+    components: Vec<(SimAlgo, f64)>,
+    aggregation: Aggregation,
+}
+
+impl Ensemble {
+    /// `components` - the algorithms with their weights
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidConfig` if there are no components, a weight is negative
+    /// or not finite, or the weights of `Aggregation::Weighted` sum up to zero
+    pub fn new(components: Vec<(SimAlgo, f64)>, aggregation: Aggregation) -> Result<Self, Error> {
+        if components.is_empty() {
+            return Err(Error::InvalidConfig(
+                "ensemble must have at least one component".to_string(),
+            ));
+        }
+        if let Some((algo, weight)) = components
+            .iter()
+            .find(|(_, weight)| !weight.is_finite() || *weight < 0.0)
+        {
+            return Err(Error::InvalidConfig(format!(
+                "invalid weight {} of {:?}",
+                weight, algo
+            )));
+        }
+        if aggregation == Aggregation::Weighted
+            && components.iter().map(|(_, weight)| weight).sum::<f64>() <= 0.0
+        {
+            return Err(Error::InvalidConfig(
+                "weights of ensemble must not sum up to zero".to_string(),
+            ));
+        }
+        Ok(Self {
+            components,
+            aggregation,
+        })
+    }
+
+    /// The same as `Ensemble::new` with the equal weights
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidConfig` if `algos` is empty
+    pub fn of(algos: &[SimAlgo], aggregation: Aggregation) -> Result<Self, Error> {
+        Self::new(algos.iter().map(|algo| (*algo, 1.0)).collect(), aggregation)
+    }
+
+    #[inline]
+    fn aggregate(&self, scores: &[(SimAlgo, f64)]) -> f64 {
+        let scores = scores.iter().map(|(_, score)| *score);
+        match self.aggregation {
+            Aggregation::Weighted => {
+                scores
+                    .zip(&self.components)
+                    .map(|(score, (_, weight))| score * weight)
+                    .sum::<f64>()
+                    / self
+                        .components
+                        .iter()
+                        .map(|(_, weight)| weight)
+                        .sum::<f64>()
+            }
+            Aggregation::Max => scores.fold(f64::MIN, f64::max),
+            Aggregation::Min => scores.fold(f64::MAX, f64::min),
+        }
+    }
+}
+
+impl Similarity for Ensemble {
+    fn similarity(&self, lhs: &str, rhs: &str) -> f64 {
+        self.aggregate(
+            &self
+                .components
+                .iter()
+                .map(|(algo, _)| (*algo, algo.similarity(lhs, rhs)))
+                .collect::<Vec<(SimAlgo, f64)>>(),
+        )
+    }
+
+    fn compare(&self, target: &Text, candidate: &Text) -> f64 {
+        self.scores(target, candidate).0
+    }
+
+    fn scores(&self, target: &Text, candidate: &Text) -> (f64, Vec<(SimAlgo, f64)>) {
+        let scores = self
+            .components
+            .iter()
+            .map(|(algo, _)| (*algo, algo.compare(target, candidate)))
+            .collect::<Vec<(SimAlgo, f64)>>();
+        (self.aggregate(&scores), scores)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate_scores() {
+        let (lhs, rhs) = (
+            Text::new("quai du seujet".to_string()),
+            Text::new("qu du seujet".to_string()),
+        );
+        let jaro_winkler = SimAlgo::JaroWinkler.compare(&lhs, &rhs);
+        let levenshtein = SimAlgo::Levenshtein.compare(&lhs, &rhs);
+        let algos = [SimAlgo::JaroWinkler, SimAlgo::Levenshtein];
+        let (similarity, scores) = Ensemble::of(&algos, Aggregation::Max)
+            .unwrap()
+            .scores(&lhs, &rhs);
+        assert_eq!(similarity, jaro_winkler.max(levenshtein));
+        assert_eq!(
+            scores,
+            vec![
+                (SimAlgo::JaroWinkler, jaro_winkler),
+                (SimAlgo::Levenshtein, levenshtein)
+            ]
+        );
+        assert_eq!(
+            Ensemble::of(&algos, Aggregation::Min)
+                .unwrap()
+                .compare(&lhs, &rhs),
+            jaro_winkler.min(levenshtein)
+        );
+        let weighted = Ensemble::new(
+            vec![(SimAlgo::JaroWinkler, 3.0), (SimAlgo::Levenshtein, 1.0)],
+            Aggregation::Weighted,
+        )
+        .unwrap();
+        assert!(
+            (weighted.compare(&lhs, &rhs) - (0.75 * jaro_winkler + 0.25 * levenshtein)).abs()
+                < 1e-10
+        );
+    }
+
+    #[test]
+    fn invalid_ensemble() {
+        assert!(matches!(
+            Ensemble::of(&[], Aggregation::Max),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Ensemble::new(vec![(SimAlgo::Jaro, -1.0)], Aggregation::Max),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Ensemble::new(vec![(SimAlgo::Jaro, 0.0)], Aggregation::Weighted),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+//! The similarity algorithms working on extended grapheme clusters instead of chars,
+//! so 'a' followed by a combining diaeresis is a single element as 'ä' is
+use std::{borrow::Cow, collections::HashMap};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits `text` into graphemes, each composed to NFC to be equal whatever form it came in
+#[inline]
+fn graphemes(text: &str) -> Vec<Cow<'_, str>> {
+    text.graphemes(true)
+        .map(|grapheme| {
+            if grapheme.chars().nth(1).is_none() {
+                Cow::Borrowed(grapheme)
+            } else {
+                Cow::Owned(grapheme.nfc().collect())
+            }
+        })
+        .collect()
+}
+
+#[inline]
+fn normalize(distance: usize, a: &[Cow<str>], b: &[Cow<str>]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    1.0 - (distance as f64) / (a.len().max(b.len()) as f64)
+}
+
+pub(super) fn levenshtein(a: &str, b: &str) -> f64 {
+    let (a, b) = (graphemes(a), graphemes(b));
+    normalize(strsim::generic_levenshtein(&a, &b), &a, &b)
+}
+
+pub(super) fn damerau_levenshtein(a: &str, b: &str) -> f64 {
+    let (a, b) = (graphemes(a), graphemes(b));
+    normalize(strsim::generic_damerau_levenshtein(&a, &b), &a, &b)
+}
+
+pub(super) fn jaro(a: &str, b: &str) -> f64 {
+    strsim::generic_jaro(&graphemes(a), &graphemes(b))
+}
+
+pub(super) fn jaro_winkler(a: &str, b: &str) -> f64 {
+    strsim::generic_jaro_winkler(&graphemes(a), &graphemes(b))
+}
+
+pub(super) fn osa(a: &str, b: &str) -> f64 {
+    let (a, b) = (graphemes(a), graphemes(b));
+    normalize(osa_distance(&a, &b), &a, &b)
+}
+
+/// The same as `strsim::osa_distance`, but on any slice
+fn osa_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let mut prev_two_distances = (0..=b.len()).collect::<Vec<usize>>();
+    let mut prev_distances = prev_two_distances.clone();
+    let mut curr_distances = vec![0; b.len() + 1];
+    for (i, a_elem) in a.iter().enumerate() {
+        curr_distances[0] = i + 1;
+        for (j, b_elem) in b.iter().enumerate() {
+            let cost = usize::from(a_elem != b_elem);
+            curr_distances[j + 1] = (curr_distances[j] + 1)
+                .min(prev_distances[j + 1] + 1)
+                .min(prev_distances[j] + cost);
+            if i > 0 && j > 0 && a_elem != b_elem && a_elem == &b[j - 1] && b_elem == &a[i - 1] {
+                curr_distances[j + 1] = curr_distances[j + 1].min(prev_two_distances[j - 1] + 1);
+            }
+        }
+        prev_two_distances.clone_from(&prev_distances);
+        prev_distances.clone_from(&curr_distances);
+    }
+    prev_distances[b.len()]
+}
+
+/// The same as `strsim::sorensen_dice`, but on bigrams of graphemes
+pub(super) fn sorensen_dice(a: &str, b: &str) -> f64 {
+    let a = graphemes(a)
+        .into_iter()
+        .filter(|grapheme| !grapheme.trim().is_empty())
+        .collect::<Vec<Cow<str>>>();
+    let b = graphemes(b)
+        .into_iter()
+        .filter(|grapheme| !grapheme.trim().is_empty())
+        .collect::<Vec<Cow<str>>>();
+    if a.is_empty() && b.is_empty() || a == b {
+        return 1.0;
+    }
+    if a.len() < 2 || b.len() < 2 {
+        return 0.0;
+    }
+    let mut a_bigrams = HashMap::new();
+    for bigram in a.windows(2) {
+        *a_bigrams.entry(bigram).or_insert(0) += 1;
+    }
+    let mut intersection_size = 0;
+    for bigram in b.windows(2) {
+        if let Some(count) = a_bigrams.get_mut(bigram) {
+            if *count > 0 {
+                *count -= 1;
+                intersection_size += 1;
+            }
+        }
+    }
+    (2 * intersection_size) as f64 / (a.len() + b.len() - 2) as f64
+}
@@ -0,0 +1,173 @@
+//! Bit-parallel Levenshtein distance (Myers, 1999) of a prepared target with the candidates.
+//! A column of the distance matrix is a pair of bit vectors, so a candidate char costs a few
+//! word operations, and the computation stops as soon as the threshold can not be reached
+use super::{PreparedQuery, SimAlgo};
+use crate::candidate::{Sens, Text};
+use std::collections::HashMap;
+
+/// The longest target fitting into a single machine word
+const MAX_PATTERN_LEN: usize = 64;
+
+pub(super) struct PreparedLevenshtein<'a> {
+    target: &'a Text,
+    len: usize,
+    ascii: [u64; 128],
+    others: HashMap<char, u64>,
+}
+
+impl<'a> PreparedLevenshtein<'a> {
+    pub(super) fn new(target: &'a Text) -> Self {
+        let mut prepared = Self {
+            target,
+            len: target.cleaned.chars().count(),
+            ascii: [0; 128],
+            others: HashMap::new(),
+        };
+        if prepared.len <= MAX_PATTERN_LEN {
+            for (idx, ch) in target.cleaned.chars().enumerate() {
+                match prepared.ascii.get_mut(ch as usize) {
+                    Some(mask) => *mask |= 1 << idx,
+                    None => *prepared.others.entry(ch).or_default() |= 1 << idx,
+                }
+            }
+        }
+        prepared
+    }
+
+    /// The bits of the target positions equal to `ch`
+    #[inline]
+    fn peq(&self, ch: char) -> u64 {
+        match self.ascii.get(ch as usize) {
+            Some(mask) => *mask,
+            None => self.others.get(&ch).copied().unwrap_or_default(),
+        }
+    }
+
+    /// The distance to `candidate`, or `None` as soon as it is known to be
+    /// larger than `max_distance`
+    fn distance_within(&self, candidate: &str, max_distance: usize) -> Option<usize> {
+        let len = candidate.chars().count();
+        if self.len.abs_diff(len) > max_distance {
+            return None;
+        }
+        if self.len == 0 {
+            return Some(len);
+        }
+        let last = 1 << (self.len - 1);
+        let (mut pv, mut mv, mut score) = (!0_u64, 0_u64, self.len);
+        for (idx, ch) in candidate.chars().enumerate() {
+            let eq = self.peq(ch);
+            let xv = eq | mv;
+            let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+            let ph = mv | !(xh | pv);
+            let mh = pv & xh;
+            if ph & last != 0 {
+                score += 1;
+            } else if mh & last != 0 {
+                score -= 1;
+            }
+            // The distance decreases by one per remaining char at most
+            if score > max_distance + (len - idx - 1) {
+                return None;
+            }
+            let ph = (ph << 1) | 1;
+            let mh = mh << 1;
+            pv = mh | !(xv | ph);
+            mv = ph & xv;
+        }
+        (score <= max_distance).then_some(score)
+    }
+
+    /// The same as `strsim::normalized_levenshtein`
+    #[inline]
+    fn similarity(distance: usize, lhs_len: usize, rhs_len: usize) -> f64 {
+        if lhs_len == 0 && rhs_len == 0 {
+            return 1.0;
+        }
+        1.0 - (distance as f64) / (lhs_len.max(rhs_len) as f64)
+    }
+}
+
+impl PreparedQuery for PreparedLevenshtein<'_> {
+    fn compare(&self, candidate: &Text) -> f64 {
+        if self.len > MAX_PATTERN_LEN {
+            return SimAlgo::Levenshtein.with(Default::default())(
+                &self.target.cleaned,
+                &candidate.cleaned,
+            );
+        }
+        let len = candidate.cleaned.chars().count();
+        let distance = self
+            .distance_within(&candidate.cleaned, usize::MAX / 2)
+            .unwrap_or_default();
+        Self::similarity(distance, self.len, len)
+    }
+
+    fn scores_above(&self, candidate: &Text, sens: Sens) -> Option<(f64, Vec<(SimAlgo, f64)>)> {
+        if self.len > MAX_PATTERN_LEN {
+            let similarity = self.compare(candidate);
+            return (similarity - sens.0 > 0.0).then_some((similarity, Vec::new()));
+        }
+        let len = candidate.cleaned.chars().count();
+        // The largest distance still more similar than `sens`, the similarity is
+        // computed the same way as for the result, so no candidate is lost by rounding
+        let max_len = self.len.max(len);
+        let max_distance = (0..=max_len)
+            .rev()
+            .find(|distance| Self::similarity(*distance, self.len, len) - sens.0 > 0.0)?;
+        let distance = self.distance_within(&candidate.cleaned, max_distance)?;
+        Some((Self::similarity(distance, self.len, len), Vec::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXTS: [&str; 12] = [
+        "",
+        "a",
+        "quaiduseujet",
+        "quduseujet",
+        "uaiduseujte",
+        "bernstrasse",
+        "chemindesaintcierges",
+        "zürich",
+        "zu\u{308}rich",
+        "pfäffikon",
+        "avenuegénéralguisanavenuegénéralguisanavenuegénéralguisanavenuegénéral",
+        "avenuegénéralguisanavenuegénéralguisanavenuegénéralguisanavenuegénéraux",
+    ];
+
+    #[test]
+    fn same_as_strsim() {
+        for target in TEXTS {
+            let target = Text::new(target.to_string());
+            let prepared = PreparedLevenshtein::new(&target);
+            for candidate in TEXTS {
+                let candidate = Text::new(candidate.to_string());
+                let expected = strsim::normalized_levenshtein(&target.cleaned, &candidate.cleaned);
+                assert_eq!(prepared.compare(&candidate), expected);
+                for sens in [0.1, 0.5, 0.7, 0.9, 1.0] {
+                    assert_eq!(
+                        prepared
+                            .scores_above(&candidate, Sens(sens))
+                            .map(|(similarity, _)| similarity),
+                        (expected - sens > 0.0).then_some(expected),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn abandon_early() {
+        let target = Text::new("quaiduseujet".to_string());
+        let prepared = PreparedLevenshtein::new(&target);
+        assert_eq!(prepared.distance_within("quaiduseujet", 0), Some(0));
+        assert_eq!(prepared.distance_within("quduseujet", 2), Some(2));
+        assert_eq!(prepared.distance_within("quduseujet", 1), None);
+        assert_eq!(prepared.distance_within("xxxxxxxxxxxx", 3), None);
+        assert_eq!(prepared.distance_within("q", 3), None);
+    }
+}
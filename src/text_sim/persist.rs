@@ -0,0 +1,52 @@
+//! Little-endian helpers for the indexes persisted to disk
+use std::io::{self, prelude::*};
+
+/// The counts read from a file are not trusted, the collections grow beyond it as they are read
+const MAX_CAPACITY: usize = 1 << 16;
+
+/// The capacity to reserve for `len` items read from a file
+#[inline]
+pub(super) fn capacity(len: usize) -> usize {
+    len.min(MAX_CAPACITY)
+}
+
+#[inline]
+pub(super) fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+#[inline]
+pub(super) fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[inline]
+pub(super) fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+#[inline]
+pub(super) fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[inline]
+pub(super) fn write_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u32(writer, value.len() as u32)?;
+    writer.write_all(value.as_bytes())
+}
+
+/// Returns `None` if the bytes are not valid UTF-8
+#[inline]
+pub(super) fn read_str(reader: &mut impl Read) -> io::Result<Option<String>> {
+    let len = read_u32(reader)? as usize;
+    let mut bytes = Vec::with_capacity(capacity(len));
+    if reader.take(len as u64).read_to_end(&mut bytes)? < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(String::from_utf8(bytes).ok())
+}
@@ -0,0 +1,261 @@
+//! Phonetic encoders for the typos made by ear: 'Langsamstig' sounds as 'Langsamsteig'.
+//! Kölner Phonetik is used for German and a Soundex adapted to French for French names
+use crate::candidate::Text;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// The words marking a French street name, used by `Phonetic::Auto`
+const FRENCH_WORDS: &[&str] = &[
+    "allée",
+    "avenue",
+    "boulevard",
+    "chemin",
+    "de",
+    "des",
+    "du",
+    "impasse",
+    "la",
+    "le",
+    "les",
+    "place",
+    "quai",
+    "route",
+    "rue",
+    "ruelle",
+    "sentier",
+];
+const FRENCH_LETTERS: &[char] = &['é', 'è', 'ê', 'à', 'â', 'ç', 'ô', 'î', 'û', 'ë', 'ï'];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phonetic {
+    /// Kölner Phonetik, for German
+    Cologne,
+    /// Soundex with the French spelling rules, for French
+    SoundexFr,
+    /// Chooses the encoder by the language of the candidate
+    #[default]
+    Auto,
+}
+
+impl Phonetic {
+    /// Encodes every word of `text` separately, the codes are separated by a space
+    pub fn encode(self, text: &str) -> String {
+        let encode = match self.resolve(text) {
+            Self::SoundexFr => soundex_fr,
+            _ => cologne,
+        };
+        text.split_whitespace()
+            .map(encode)
+            .filter(|code| !code.is_empty())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// The encoder to use for `text`: `Auto` is resolved to `SoundexFr` for the French texts
+    /// and to `Cologne` for all others
+    pub fn resolve(self, text: &str) -> Self {
+        match self {
+            Self::Auto if is_french(text) => Self::SoundexFr,
+            Self::Auto => Self::Cologne,
+            phonetic => phonetic,
+        }
+    }
+
+    /// Similarity of the codes, `Auto` encodes both texts by the language of `candidate`
+    pub fn similarity(self, target: &str, candidate: &str) -> f64 {
+        let phonetic = self.resolve(candidate);
+        strsim::normalized_levenshtein(&phonetic.encode(target), &phonetic.encode(candidate))
+    }
+}
+
+/// Keeps only the candidates sounding the same as the target,
+/// the target is encoded once per encoder
+pub(super) struct PhoneticFilter {
+    phonetic: Phonetic,
+    cologne: String,
+    soundex_fr: String,
+}
+
+impl PhoneticFilter {
+    pub(super) fn new(phonetic: Phonetic, target: &Text) -> Self {
+        Self {
+            phonetic,
+            cologne: Phonetic::Cologne.encode(&target.words),
+            soundex_fr: Phonetic::SoundexFr.encode(&target.words),
+        }
+    }
+
+    pub(super) fn accepts(&self, candidate: &Text) -> bool {
+        let phonetic = self.phonetic.resolve(&candidate.words);
+        let target = match phonetic {
+            Phonetic::SoundexFr => &self.soundex_fr,
+            _ => &self.cologne,
+        };
+        target == &phonetic.encode(&candidate.words)
+    }
+}
+
+#[inline]
+fn is_french(text: &str) -> bool {
+    let text = text.to_lowercase();
+    text.contains(FRENCH_LETTERS)
+        || text
+            .split(|ch: char| !ch.is_alphabetic())
+            .any(|word| FRENCH_WORDS.contains(&word))
+}
+
+/// Lowercases `word` and keeps only its letters without diacritics, 'ß' is kept as 's'
+#[inline]
+fn letters(word: &str) -> Vec<char> {
+    word.to_lowercase()
+        .replace('ß', "s")
+        .nfd()
+        .filter(|ch| !is_combining_mark(*ch) && ch.is_ascii_alphabetic())
+        .collect()
+}
+
+/// Kölner Phonetik: 'Müller-Lüdenscheidt' -> '65752682'
+pub fn cologne(word: &str) -> String {
+    let letters = letters(word);
+    let mut codes = String::new();
+    for (idx, ch) in letters.iter().enumerate() {
+        let prev = idx.checked_sub(1).map(|idx| letters[idx]);
+        let next = letters.get(idx + 1).copied();
+        let is_next = |chars: &str| next.is_some_and(|next| chars.contains(next));
+        let is_prev = |chars: &str| prev.is_some_and(|prev| chars.contains(prev));
+        let code = match ch {
+            'a' | 'e' | 'i' | 'j' | 'o' | 'u' | 'y' => "0",
+            'h' => "",
+            'b' => "1",
+            'p' if is_next("h") => "3",
+            'p' => "1",
+            'd' | 't' if is_next("csz") => "8",
+            'd' | 't' => "2",
+            'f' | 'v' | 'w' => "3",
+            'g' | 'k' | 'q' => "4",
+            'c' if idx == 0 && is_next("ahkloqrux") => "4",
+            'c' if idx == 0 => "8",
+            'c' if is_prev("sz") => "8",
+            'c' if is_next("ahkoqux") => "4",
+            'c' => "8",
+            'x' if is_prev("ckq") => "8",
+            'x' => "48",
+            'l' => "5",
+            'm' | 'n' => "6",
+            'r' => "7",
+            's' | 'z' => "8",
+            _ => "",
+        };
+        codes.push_str(code);
+    }
+    let mut result = String::with_capacity(codes.len());
+    let mut last = None;
+    for (idx, code) in codes.chars().enumerate() {
+        if last != Some(code) && (code != '0' || idx == 0) {
+            result.push(code);
+        }
+        last = Some(code);
+    }
+    result
+}
+
+/// Soundex with the French spelling rules and without truncation:
+/// 'Chasseurs' -> 's86' as 'Chasseur'
+pub fn soundex_fr(word: &str) -> String {
+    let mut word = letters(word).into_iter().collect::<String>();
+    for (spelling, sound) in [
+        ("eau", "o"),
+        ("au", "o"),
+        ("ph", "f"),
+        ("qu", "k"),
+        ("sch", "s"),
+        ("ch", "s"),
+        ("gn", "n"),
+        ("ck", "k"),
+        ("ce", "se"),
+        ("ci", "si"),
+        ("cy", "sy"),
+        ("ge", "je"),
+        ("gi", "ji"),
+        ("gy", "jy"),
+    ] {
+        word = word.replace(spelling, sound);
+    }
+    // The silent endings: 'chasseurs', 'prix', 'rue'
+    let word = word.trim_end_matches(['s', 'x', 'z']);
+    let word = if word.len() > 1 {
+        word.trim_end_matches('e')
+    } else {
+        word
+    };
+    let mut chars = word.chars();
+    let Some(first) = chars.next() else {
+        return String::new();
+    };
+    let mut result = first.to_string();
+    let mut last = soundex_fr_code(first);
+    for ch in chars {
+        let code = soundex_fr_code(ch);
+        if let Some(code) = code.filter(|code| Some(*code) != last) {
+            result.push(code);
+        }
+        // A vowel separates the same codes, 'h' and 'w' do not
+        if code.is_some() || !"hw".contains(ch) {
+            last = code;
+        }
+    }
+    result
+}
+
+#[inline]
+fn soundex_fr_code(ch: char) -> Option<char> {
+    match ch {
+        'b' | 'p' => Some('1'),
+        'c' | 'k' | 'q' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        'g' | 'j' => Some('7'),
+        's' | 'x' | 'z' => Some('8'),
+        'f' | 'v' => Some('9'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cologne_codes() {
+        assert_eq!(cologne("Müller-Lüdenscheidt"), "65752682");
+        assert_eq!(cologne("Wikipedia"), "3412");
+        assert_eq!(cologne("Gotzäckerstrassse"), cologne("Gotzäckerstrasse"));
+        assert_eq!(cologne("Langsamstig"), cologne("Langsamsteig"));
+        assert_eq!(cologne(""), "");
+    }
+
+    #[test]
+    fn soundex_fr_codes() {
+        assert_eq!(soundex_fr("Chasseurs"), "s86");
+        assert_eq!(soundex_fr("Chasseur"), soundex_fr("Chasseurs"));
+        assert_eq!(soundex_fr("Beaulieu"), soundex_fr("Bolieu"));
+        assert_eq!(soundex_fr("Philosophes"), soundex_fr("Filosofes"));
+        assert_ne!(soundex_fr("Rottes"), soundex_fr("Rosses"));
+        assert_eq!(soundex_fr(""), "");
+    }
+
+    #[test]
+    fn choose_by_language() {
+        assert_eq!(
+            Phonetic::Auto.resolve("chemin du chasseur"),
+            Phonetic::SoundexFr
+        );
+        assert_eq!(Phonetic::Auto.resolve("langsamsteig"), Phonetic::Cologne);
+        assert_eq!(Phonetic::Cologne.resolve("rue du clos"), Phonetic::Cologne);
+        assert_eq!(
+            Phonetic::Auto.similarity("chemin du chasseur", "chemin des chasseurs"),
+            Phonetic::SoundexFr.similarity("chemin du chasseur", "chemin des chasseurs")
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! The extension point of the matcher: anything implementing `Similarity` can score the candidates
+use super::{myers::PreparedLevenshtein, Segmentation, SimAlgo, SimFunc};
+use crate::candidate::{Sens, Text};
+
+pub trait Similarity: Send + Sync {
+    /// Similarity of two cleaned texts from 0.0 (different) to 1.0 (the same)
+    fn similarity(&self, lhs: &str, rhs: &str) -> f64;
+
+    /// One-off comparison of the target with a candidate
+    fn compare(&self, target: &Text, candidate: &Text) -> f64 {
+        self.similarity(&target.cleaned, &candidate.cleaned)
+    }
+
+    /// The same as `compare`, but also returns the scores of the components if the scorer
+    /// combines several algorithms, see `Ensemble`
+    fn scores(&self, target: &Text, candidate: &Text) -> (f64, Vec<(SimAlgo, f64)>) {
+        (self.compare(target, candidate), Vec::new())
+    }
+
+    /// The algorithm reported on the candidates, `None` unless the scorer is a single algorithm
+    fn algo(&self) -> Option<SimAlgo> {
+        None
+    }
+
+    /// Prepares `target` for the comparison with many candidates,
+    /// a scorer can override it to precompute something per query only once
+    fn prepare<'a>(&'a self, target: &'a Text) -> Box<dyn PreparedQuery + 'a> {
+        Box::new(Unprepared {
+            similarity: self,
+            target,
+        })
+    }
+}
+
+pub trait PreparedQuery {
+    fn compare(&self, candidate: &Text) -> f64;
+
+    /// See `Similarity::scores`
+    fn scores(&self, candidate: &Text) -> (f64, Vec<(SimAlgo, f64)>) {
+        (self.compare(candidate), Vec::new())
+    }
+
+    /// The same as `scores`, but only if the similarity is larger than `sens`,
+    /// a scorer can override it to stop as soon as `sens` can not be reached
+    fn scores_above(&self, candidate: &Text, sens: Sens) -> Option<(f64, Vec<(SimAlgo, f64)>)> {
+        let scores = self.scores(candidate);
+        (scores.0 - sens.0 > 0.0).then_some(scores)
+    }
+}
+
+struct Unprepared<'a, S: ?Sized> {
+    similarity: &'a S,
+    target: &'a Text,
+}
+
+impl<'a, S: Similarity + ?Sized> PreparedQuery for Unprepared<'a, S> {
+    #[inline]
+    fn compare(&self, candidate: &Text) -> f64 {
+        self.similarity.compare(self.target, candidate)
+    }
+
+    #[inline]
+    fn scores(&self, candidate: &Text) -> (f64, Vec<(SimAlgo, f64)>) {
+        self.similarity.scores(self.target, candidate)
+    }
+}
+
+/// The token-based, phonetic and trigram algorithms compare the words of the texts,
+/// the others the cleaned texts
+#[inline]
+fn compare_by(sim_func: SimFunc, algo: SimAlgo, target: &Text, candidate: &Text) -> f64 {
+    match algo {
+        SimAlgo::TokenSort
+        | SimAlgo::TokenSet
+        | SimAlgo::PartialRatio
+        | SimAlgo::Phonetic(_)
+        | SimAlgo::TfIdf => sim_func(&target.words, &candidate.words),
+        _ => sim_func(&target.cleaned, &candidate.cleaned),
+    }
+}
+
+/// A single algorithm comparing the texts by `segmentation`, see `Config::segmentation`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segmented {
+    pub algo: SimAlgo,
+    pub segmentation: Segmentation,
+}
+
+impl Similarity for Segmented {
+    #[inline]
+    fn similarity(&self, lhs: &str, rhs: &str) -> f64 {
+        self.algo.with(self.segmentation)(lhs, rhs)
+    }
+
+    #[inline]
+    fn algo(&self) -> Option<SimAlgo> {
+        Some(self.algo)
+    }
+
+    #[inline]
+    fn compare(&self, target: &Text, candidate: &Text) -> f64 {
+        compare_by(
+            self.algo.with(self.segmentation),
+            self.algo,
+            target,
+            candidate,
+        )
+    }
+
+    fn prepare<'a>(&'a self, target: &'a Text) -> Box<dyn PreparedQuery + 'a> {
+        match self.segmentation {
+            Segmentation::Chars => self.algo.prepare(target),
+            Segmentation::Graphemes => Box::new(PreparedSegmented::new(*self, target)),
+        }
+    }
+}
+
+/// Owns the scorer, so it could be prepared from a `Segmented` made on the fly
+pub(super) struct PreparedSegmented<'a> {
+    sim_func: SimFunc,
+    algo: SimAlgo,
+    target: &'a Text,
+}
+
+impl<'a> PreparedSegmented<'a> {
+    pub(super) fn new(segmented: Segmented, target: &'a Text) -> Self {
+        Self {
+            sim_func: segmented.algo.with(segmented.segmentation),
+            algo: segmented.algo,
+            target,
+        }
+    }
+}
+
+impl PreparedQuery for PreparedSegmented<'_> {
+    #[inline]
+    fn compare(&self, candidate: &Text) -> f64 {
+        compare_by(self.sim_func, self.algo, self.target, candidate)
+    }
+}
+
+/// Any function or closure, also with a state, is a scorer: `|lhs, rhs| ...`
+impl<F> Similarity for F
+where
+    F: Fn(&str, &str) -> f64 + Send + Sync,
+{
+    #[inline]
+    fn similarity(&self, lhs: &str, rhs: &str) -> f64 {
+        self(lhs, rhs)
+    }
+}
+
+impl Similarity for SimAlgo {
+    #[inline]
+    fn similarity(&self, lhs: &str, rhs: &str) -> f64 {
+        self.with(Segmentation::Chars)(lhs, rhs)
+    }
+
+    #[inline]
+    fn algo(&self) -> Option<SimAlgo> {
+        Some(*self)
+    }
+
+    #[inline]
+    fn compare(&self, target: &Text, candidate: &Text) -> f64 {
+        compare_by(self.with(Segmentation::Chars), *self, target, candidate)
+    }
+
+    /// Levenshtein is computed bit-parallel with the early abandon at the threshold
+    fn prepare<'a>(&'a self, target: &'a Text) -> Box<dyn PreparedQuery + 'a> {
+        match self {
+            Self::Levenshtein => Box::new(PreparedLevenshtein::new(target)),
+            _ => Box::new(Unprepared {
+                similarity: self,
+                target,
+            }),
+        }
+    }
+}
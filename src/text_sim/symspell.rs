@@ -0,0 +1,312 @@
+//! Symmetric delete index (SymSpell): the candidates within `k` edits of a text share a string
+//! made by at most `k` deletions from each of them, so a lookup only hashes the deletions
+//! of the text instead of comparing it with every candidate
+use super::{
+    persist::{capacity, read_str, read_u32, read_u64, write_str, write_u32, write_u64},
+    SimAlgo,
+};
+use crate::{
+    candidate::{self, Candidate, SimResult, Text},
+    mass::Error,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, prelude::*, BufReader, BufWriter},
+    path::Path,
+};
+
+const MAGIC: &[u8; 8] = b"MASSSYM1";
+
+#[derive(Debug, Clone)]
+pub struct SymSpellIndex {
+    max_distance: usize,
+    texts: Vec<Text>,
+    /// The hashes of the deletions are stored instead of the strings to save memory,
+    /// a collision only adds a candidate which is verified anyway
+    deletes: HashMap<u64, Vec<u32>>,
+}
+
+impl SymSpellIndex {
+    /// The number of deletions grows exponentially with the distance,
+    /// so a larger `max_distance` is lowered to it
+    pub const MAX_DISTANCE: usize = 3;
+
+    /// `max_distance` - the largest OSA distance a lookup can search within,
+    /// at most `MAX_DISTANCE`; the candidates are cleaned by `Text::new`
+    pub fn new(candidates: Vec<String>, max_distance: usize) -> Self {
+        let max_distance = max_distance.min(Self::MAX_DISTANCE);
+        let mut deletes = HashMap::<u64, Vec<u32>>::new();
+        let texts = candidates.into_iter().map(Text::new).collect::<Vec<Text>>();
+        for (id, text) in texts.iter().enumerate() {
+            for delete in deletions(&text.cleaned, max_distance) {
+                deletes.entry(hash(&delete)).or_default().push(id as u32);
+            }
+        }
+        Self {
+            max_distance,
+            texts,
+            deletes,
+        }
+    }
+
+    /// Builds the index from a file with a candidate on each line, e.g. `street_names.txt`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file could not be read
+    pub fn from_file(file: &Path, max_distance: usize) -> Result<Self, Error> {
+        Ok(Self::new(
+            BufReader::new(File::open(file)?)
+                .lines()
+                .collect::<io::Result<Vec<String>>>()?,
+            max_distance,
+        ))
+    }
+
+    pub fn max_distance(&self) -> usize {
+        self.max_distance
+    }
+
+    pub fn len(&self) -> usize {
+        self.texts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty()
+    }
+
+    /// The candidates within `max_distance` OSA edits of `text` (at most the distance
+    /// the index was built with), the most similar first as scored by `SimAlgo::Osa`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::NotFound` if there is no such candidate
+    pub fn lookup(&self, text: &Text, max_distance: usize, num_to_keep: usize) -> SimResult {
+        let max_distance = max_distance.min(self.max_distance);
+        let mut ids = deletions(&text.cleaned, max_distance)
+            .iter()
+            .flat_map(|delete| self.deletes.get(&hash(delete)).into_iter().flatten())
+            .copied()
+            .collect::<HashSet<u32>>()
+            .into_iter()
+            .collect::<Vec<u32>>();
+        ids.sort_unstable();
+        let target_len = text.cleaned.chars().count();
+        candidate::keep_best(
+            ids.into_iter()
+                .map(|id| (id as usize, &self.texts[id as usize]))
+                .flat_map(|(id, candidate)| {
+                    let len = candidate.cleaned.chars().count();
+                    if target_len.abs_diff(len) > max_distance {
+                        return None;
+                    }
+                    let distance = strsim::osa_distance(&text.cleaned, &candidate.cleaned);
+                    (distance <= max_distance).then(|| Candidate {
+                        text: candidate.init.to_owned(),
+                        cleaned: candidate.cleaned.to_owned(),
+                        similarity: if distance == 0 {
+                            1.0
+                        } else {
+                            1.0 - distance as f64 / target_len.max(len) as f64
+                        },
+                        index: Some(id),
+                        algo: Some(SimAlgo::Osa),
+                        ..Default::default()
+                    })
+                }),
+            num_to_keep,
+        )
+    }
+
+    /// Persists the index, so it is not built again on each start
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file could not be written
+    pub fn save(&self, file: &Path) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(file)?);
+        writer.write_all(MAGIC)?;
+        write_u32(&mut writer, self.max_distance as u32)?;
+        write_u32(&mut writer, self.texts.len() as u32)?;
+        for text in &self.texts {
+            write_str(&mut writer, &text.init)?;
+        }
+        write_u32(&mut writer, self.deletes.len() as u32)?;
+        for (hash, ids) in &self.deletes {
+            write_u64(&mut writer, *hash)?;
+            write_u32(&mut writer, ids.len() as u32)?;
+            for id in ids {
+                write_u32(&mut writer, *id)?;
+            }
+        }
+        Ok(writer.flush()?)
+    }
+
+    /// Loads the index persisted by `SymSpellIndex::save`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file could not be read
+    /// and `Error::InvalidConfig` if it is not a persisted index
+    pub fn load(file: &Path) -> Result<Self, Error> {
+        let invalid =
+            || Error::InvalidConfig(format!("{} is not a SymSpell index", file.display()));
+        let mut reader = BufReader::new(File::open(file)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid());
+        }
+        let max_distance = read_u32(&mut reader)? as usize;
+        if max_distance > Self::MAX_DISTANCE {
+            return Err(invalid());
+        }
+        let num_of_texts = read_u32(&mut reader)? as usize;
+        let mut texts = Vec::with_capacity(capacity(num_of_texts));
+        for _ in 0..num_of_texts {
+            texts.push(Text::new(read_str(&mut reader)?.ok_or_else(invalid)?));
+        }
+        let num_of_deletes = read_u32(&mut reader)? as usize;
+        let mut deletes = HashMap::with_capacity(capacity(num_of_deletes));
+        for _ in 0..num_of_deletes {
+            let hash = read_u64(&mut reader)?;
+            let len = read_u32(&mut reader)? as usize;
+            let mut ids = Vec::with_capacity(capacity(len));
+            for _ in 0..len {
+                let id = read_u32(&mut reader)?;
+                if id as usize >= num_of_texts {
+                    return Err(invalid());
+                }
+                ids.push(id);
+            }
+            deletes.insert(hash, ids);
+        }
+        Ok(Self {
+            max_distance,
+            texts,
+            deletes,
+        })
+    }
+}
+
+/// `text` and every string made by deleting up to `max_distance` chars from it
+fn deletions(text: &str, max_distance: usize) -> HashSet<String> {
+    let mut deletes = HashSet::from([text.to_string()]);
+    let mut last = vec![text.chars().collect::<Vec<char>>()];
+    for _ in 0..max_distance {
+        let mut next = Vec::new();
+        for chars in &last {
+            for idx in 0..chars.len() {
+                let mut delete = chars.clone();
+                delete.remove(idx);
+                if deletes.insert(delete.iter().collect()) {
+                    next.push(delete);
+                }
+            }
+        }
+        last = next;
+    }
+    deletes
+}
+
+/// FNV-1a, stable across the builds unlike the `std` hasher, so the hashes can be persisted
+#[inline]
+fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        candidate::Sens,
+        text_sim::{cmp_with_arr, Config, SimAlgo},
+    };
+    use std::env;
+
+    const DATA_FILE: &str = "./test_data/streets_data/street_names.txt";
+
+    #[test]
+    fn correct_typos() {
+        let index = SymSpellIndex::from_file(Path::new(DATA_FILE), 2).unwrap();
+        for text in ["quai du seujte", "qai du seujet", "quai du seujet"] {
+            assert_eq!(
+                index.lookup(&Text::new(text.to_string()), 2, 1).unwrap()[0].text,
+                "quai du seujet"
+            );
+        }
+        assert!(index
+            .lookup(&Text::new("quai du seujet".to_string()), 5, 1)
+            .is_ok());
+        assert!(matches!(
+            index.lookup(&Text::new("xyz".to_string()), 2, 1),
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[test]
+    fn same_as_linear_scan() {
+        let names = BufReader::new(File::open(DATA_FILE).unwrap())
+            .lines()
+            .collect::<io::Result<Vec<String>>>()
+            .unwrap();
+        let index = SymSpellIndex::new(names.clone(), 2);
+        for text in ["bernstrase", "aarstrase", "avenue de la gar"] {
+            let text = Text::new(text.to_string());
+            let cfg = Config::new(Sens(1e-9), names.len(), SimAlgo::Osa, None);
+            let expected = cmp_with_arr(&names, &text, &cfg)
+                .unwrap()
+                .into_iter()
+                .filter(|candidate| {
+                    strsim::osa_distance(
+                        &text.cleaned,
+                        &Text::new(candidate.text.to_owned()).cleaned,
+                    ) <= 2
+                })
+                .collect::<Vec<Candidate>>();
+            assert_eq!(index.lookup(&text, 2, names.len()).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn max_distance_is_bounded() {
+        let index = SymSpellIndex::new(vec!["bernstrasse".to_string()], 100);
+        assert_eq!(index.max_distance(), SymSpellIndex::MAX_DISTANCE);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let index = SymSpellIndex::from_file(Path::new(DATA_FILE), 1).unwrap();
+        let file = env::temp_dir().join(format!("mass_symspell_{}", std::process::id()));
+        index.save(&file).unwrap();
+        let loaded = SymSpellIndex::load(&file).unwrap();
+        let text = Text::new("bernstrase".to_string());
+        assert_eq!(loaded.max_distance(), 1);
+        assert_eq!(
+            loaded.lookup(&text, 1, 5).unwrap(),
+            index.lookup(&text, 1, 5).unwrap()
+        );
+        std::fs::write(&file, "MASSTRI1").unwrap();
+        assert!(matches!(
+            SymSpellIndex::load(&file),
+            Err(Error::InvalidConfig(_))
+        ));
+        // The distance and the counts are not trusted
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&file, &bytes).unwrap();
+        assert!(matches!(
+            SymSpellIndex::load(&file),
+            Err(Error::InvalidConfig(_))
+        ));
+        bytes.truncate(MAGIC.len());
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&file, &bytes).unwrap();
+        assert!(matches!(SymSpellIndex::load(&file), Err(Error::Io(_))));
+        std::fs::remove_file(file).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+//! Layout of a candidates file: a plain list with a candidate per line
+//! or delimited rows (TSV, CSV) with the candidate in one of the columns
+
+/// The default layout is a plain list, e.g. `places.txt`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FileFormat {
+    /// The separator of the columns, `None` if the whole line is the candidate
+    pub delimiter: Option<char>,
+    /// The column compared with the target text, counted from 0
+    pub match_column: usize,
+    /// If set, the first line names the columns and is not compared
+    pub has_header: bool,
+    /// If set, a field could be quoted to keep the delimiters inside it, as in CSV
+    pub is_quoted: bool,
+    /// If set, a row without the match column fails the whole lookup instead of being skipped
+    pub is_strict: bool,
+}
+
+impl FileFormat {
+    /// A candidate per line
+    pub fn lines() -> Self {
+        Self::default()
+    }
+
+    /// Tab separated columns, the quotes are a part of the fields
+    pub fn tsv(match_column: usize) -> Self {
+        Self {
+            delimiter: Some('\t'),
+            match_column,
+            ..Self::default()
+        }
+    }
+
+    /// Comma separated columns, a field could be quoted: `"Rue du Rhône, 2"`
+    pub fn csv(match_column: usize) -> Self {
+        Self {
+            delimiter: Some(','),
+            match_column,
+            is_quoted: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_header(self) -> Self {
+        Self {
+            has_header: true,
+            ..self
+        }
+    }
+
+    /// Fails the lookup with `Error::InvalidRow` at the first row without the match column
+    pub fn strict(self) -> Self {
+        Self {
+            is_strict: true,
+            ..self
+        }
+    }
+
+    /// Splits `line` into the candidate and the other columns in their order,
+    /// or returns the reason why the row is not valid
+    pub(super) fn parse_row(&self, line: String) -> Result<(String, Vec<String>), String> {
+        let Some(delimiter) = self.delimiter else {
+            return Ok((line, Vec::new()));
+        };
+        let mut columns = if self.is_quoted {
+            split_quoted(&line, delimiter)
+                .ok_or_else(|| format!("unterminated quote in '{}'", line))?
+        } else {
+            line.split(delimiter).map(str::to_string).collect()
+        };
+        if self.match_column >= columns.len() {
+            return Err(format!("no column {} in '{}'", self.match_column, line));
+        }
+        let candidate = columns.remove(self.match_column);
+        Ok((candidate, columns))
+    }
+}
+
+/// A quoted field keeps the delimiters inside it, a quote in it is doubled: `"a ""b"", c"`.
+/// Returns `None` if the last quoted field is not closed
+fn split_quoted(line: &str, delimiter: char) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut is_quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if is_quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if is_quoted => is_quoted = false,
+            '"' if field.is_empty() => is_quoted = true,
+            _ if c == delimiter && !is_quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    (!is_quoted).then_some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_columns() {
+        assert_eq!(
+            FileFormat::lines()
+                .parse_row("Rue du Rhône, 2".to_string())
+                .unwrap(),
+            ("Rue du Rhône, 2".to_string(), vec![])
+        );
+        assert_eq!(
+            FileFormat::tsv(1)
+                .parse_row("17\tquai du seujet\t1201".to_string())
+                .unwrap(),
+            (
+                "quai du seujet".to_string(),
+                vec!["17".to_string(), "1201".to_string()]
+            )
+        );
+        assert_eq!(
+            FileFormat::csv(0)
+                .parse_row(r#""Rue du Rhône, 2",,"le ""Kluser""""#.to_string())
+                .unwrap(),
+            (
+                "Rue du Rhône, 2".to_string(),
+                vec!["".to_string(), r#"le "Kluser""#.to_string()]
+            )
+        );
+        // Only CSV fields are quoted
+        assert_eq!(
+            FileFormat::tsv(0)
+                .parse_row("\"Rue du Rhône\t2\"\t1204".to_string())
+                .unwrap(),
+            (
+                "\"Rue du Rhône".to_string(),
+                vec!["2\"".to_string(), "1204".to_string()]
+            )
+        );
+        assert!(FileFormat::tsv(2)
+            .parse_row("17\tquai du seujet".to_string())
+            .is_err());
+        assert!(FileFormat::csv(0)
+            .parse_row("\"quai du seujet,1201".to_string())
+            .is_err());
+    }
+}
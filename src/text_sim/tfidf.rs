@@ -0,0 +1,206 @@
+//! TF-IDF weighted cosine similarity of character trigrams. The document frequencies are
+//! computed once from the candidates, so the rare parts like 'seujet' weigh more than
+//! the common ones like 'strasse' or 'chemin de la'
+use super::{PreparedQuery, SimAlgo, Similarity};
+use crate::{candidate::Text, mass::Error};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{prelude::*, BufReader},
+    path::Path,
+};
+use unicode_normalization::UnicodeNormalization;
+
+type Trigram = [char; 3];
+
+/// The weights of the trigrams of a text with its norm
+struct Vector {
+    weights: HashMap<Trigram, f64>,
+    norm: f64,
+}
+
+impl Vector {
+    fn new(text: &str, idf: impl Fn(&Trigram) -> f64) -> Self {
+        let mut weights = HashMap::new();
+        for trigram in trigrams(text) {
+            *weights.entry(trigram).or_insert(0.0) += 1.0;
+        }
+        for (trigram, weight) in weights.iter_mut() {
+            *weight *= idf(trigram);
+        }
+        let norm = weights
+            .values()
+            .map(|weight| weight * weight)
+            .sum::<f64>()
+            .sqrt();
+        Self { weights, norm }
+    }
+
+    fn cosine(&self, other: &Self) -> f64 {
+        if self.weights.is_empty() && other.weights.is_empty() {
+            return 1.0;
+        }
+        if self.norm == 0.0 || other.norm == 0.0 {
+            return 0.0;
+        }
+        let (shorter, longer) = if self.weights.len() <= other.weights.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let dot = shorter
+            .weights
+            .iter()
+            .flat_map(|(trigram, weight)| Some(weight * longer.weights.get(trigram)?))
+            .sum::<f64>();
+        (dot / (self.norm * other.norm)).min(1.0)
+    }
+}
+
+/// The trigrams of the words padded by a space: 'du' -> [' du', 'du ']
+#[inline]
+fn trigrams(text: &str) -> impl Iterator<Item = Trigram> {
+    let chars = format!(" {} ", text.trim()).nfc().collect::<Vec<char>>();
+    (0..chars.len().saturating_sub(2)).map(move |idx| [chars[idx], chars[idx + 1], chars[idx + 2]])
+}
+
+/// Cosine of the trigrams without the weighting, used by `SimAlgo::TfIdf` without an index
+pub(super) fn cosine(lhs: &str, rhs: &str) -> f64 {
+    Vector::new(lhs, |_| 1.0).cosine(&Vector::new(rhs, |_| 1.0))
+}
+
+/// The document frequencies of the trigrams of the candidates
+#[derive(Debug, Clone, Default)]
+pub struct TfIdfIndex {
+    frequencies: HashMap<Trigram, usize>,
+    num_of_docs: usize,
+}
+
+impl TfIdfIndex {
+    /// The candidates are cleaned by `Text::new`, the same way the texts are compared
+    pub fn new<I, S>(candidates: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut index = Self::default();
+        for candidate in candidates {
+            let text = Text::new(candidate.into());
+            let mut trigrams = trigrams(&text.words).collect::<Vec<Trigram>>();
+            trigrams.sort_unstable();
+            trigrams.dedup();
+            for trigram in trigrams {
+                *index.frequencies.entry(trigram).or_insert(0) += 1;
+            }
+            index.num_of_docs += 1;
+        }
+        index
+    }
+
+    /// Builds the index from a file with a candidate on each line
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file could not be read
+    pub fn from_file(file: &Path) -> Result<Self, Error> {
+        Ok(Self::new(
+            BufReader::new(File::open(file)?)
+                .lines()
+                .collect::<Result<Vec<String>, _>>()?,
+        ))
+    }
+
+    /// Smoothed inverse document frequency, an unknown trigram gets the largest one
+    #[inline]
+    fn idf(&self, trigram: &Trigram) -> f64 {
+        let frequency = self.frequencies.get(trigram).copied().unwrap_or_default();
+        ((1 + self.num_of_docs) as f64 / (1 + frequency) as f64).ln() + 1.0
+    }
+
+    #[inline]
+    fn vector(&self, text: &str) -> Vector {
+        Vector::new(text, |trigram| self.idf(trigram))
+    }
+}
+
+impl Similarity for TfIdfIndex {
+    fn similarity(&self, lhs: &str, rhs: &str) -> f64 {
+        self.vector(lhs).cosine(&self.vector(rhs))
+    }
+
+    fn compare(&self, target: &Text, candidate: &Text) -> f64 {
+        self.similarity(&target.words, &candidate.words)
+    }
+
+    fn algo(&self) -> Option<SimAlgo> {
+        Some(SimAlgo::TfIdf)
+    }
+
+    fn prepare<'a>(&'a self, target: &'a Text) -> Box<dyn PreparedQuery + 'a> {
+        Box::new(PreparedTfIdf {
+            index: self,
+            target: self.vector(&target.words),
+        })
+    }
+}
+
+/// The target vector is computed only once per query
+struct PreparedTfIdf<'a> {
+    index: &'a TfIdfIndex,
+    target: Vector,
+}
+
+impl PreparedQuery for PreparedTfIdf<'_> {
+    fn compare(&self, candidate: &Text) -> f64 {
+        self.target.cosine(&self.index.vector(&candidate.words))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        candidate::Sens,
+        text_sim::{cmp_with_arr, Config, SimAlgo},
+    };
+
+    const STREETS: [&str; 12] = [
+        "Quai du Seujet",
+        "Chemin de la Gravière",
+        "Chemin de la Chapelle",
+        "Chemin de la Seymaz",
+        "Chemin de la Sapinière",
+        "Chemin de la Montagne",
+        "Chemin de la Vendée",
+        "Chemin de la Caroline",
+        "Chemin de la Tour",
+        "Chemin de la Fontaine",
+        "Chemin de la Roseraie",
+        "Route de Chêne",
+    ];
+
+    #[test]
+    fn rare_trigrams_drive_ranking() {
+        let candidates = STREETS.map(String::from).to_vec();
+        let text = Text::new("Chemin de la Seujet".to_string());
+        let cfg = Config::new(Sens::new(0.1).unwrap(), 1, SimAlgo::TfIdf, None);
+        assert_ne!(
+            cmp_with_arr(&candidates, &text, &cfg).unwrap()[0].text,
+            "Quai du Seujet"
+        );
+        let cfg = Config::new(Sens::new(0.1).unwrap(), 1, TfIdfIndex::new(STREETS), None);
+        assert_eq!(
+            cmp_with_arr(&candidates, &text, &cfg).unwrap()[0].text,
+            "Quai du Seujet"
+        );
+    }
+
+    #[test]
+    fn cosine_bounds() {
+        let index = TfIdfIndex::new(STREETS);
+        assert!((index.similarity("quai du seujet", "quai du seujet") - 1.0).abs() < 1e-10);
+        assert_eq!(index.similarity("", ""), 1.0);
+        assert_eq!(index.similarity("abc", ""), 0.0);
+        assert_eq!(cosine("abc", "xyz"), 0.0);
+    }
+}
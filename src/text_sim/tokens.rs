@@ -0,0 +1,106 @@
+//! The similarity algorithms working on the words of a text instead of a single string,
+//! so the word order and the extra words are not penalised as much
+use super::SimFunc;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits the words of `Text::words`: 'avenue général guisan' -> ["avenue", "général", "guisan"]
+pub(super) fn words(text: &str) -> Vec<&str> {
+    text.split_whitespace().collect()
+}
+
+/// `ratio` of the texts with the sorted words:
+/// 'guisan général avenue' is compared as 'avenue général guisan'
+pub(super) fn token_sort(lhs: &[&str], rhs: &[&str], ratio: SimFunc) -> f64 {
+    ratio(&sorted(lhs).join(" "), &sorted(rhs).join(" "))
+}
+
+/// `ratio` of the common words with each text's rest,
+/// so the extra words of one of the texts do not lower the score:
+/// 'abstellplätze bombachsteig 9' and 'bombachsteig 9' are the same
+pub(super) fn token_set(lhs: &[&str], rhs: &[&str], ratio: SimFunc) -> f64 {
+    let (lhs, rhs) = (sorted(lhs), sorted(rhs));
+    let common = lhs
+        .iter()
+        .filter(|word| rhs.contains(word))
+        .copied()
+        .collect::<Vec<&str>>();
+    let with_rest = |words: &[&str]| {
+        common
+            .iter()
+            .copied()
+            .chain(words.iter().filter(|word| !common.contains(word)).copied())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    };
+    let (lhs, rhs, common) = (with_rest(&lhs), with_rest(&rhs), common.join(" "));
+    ratio(&common, &lhs)
+        .max(ratio(&common, &rhs))
+        .max(ratio(&lhs, &rhs))
+}
+
+/// The best `ratio` of the shorter text with a substring of the longer one of the same length,
+/// the lengths are counted in graphemes not to split a letter from its combining mark
+pub(super) fn partial_ratio(lhs: &[&str], rhs: &[&str], ratio: SimFunc) -> f64 {
+    let (lhs, rhs) = (lhs.join(" "), rhs.join(" "));
+    let (lhs_len, rhs_len) = (lhs.graphemes(true).count(), rhs.graphemes(true).count());
+    let (shorter, longer, len) = if lhs_len <= rhs_len {
+        (lhs, rhs, lhs_len)
+    } else {
+        (rhs, lhs, rhs_len)
+    };
+    if len == 0 {
+        return ratio(&shorter, &longer);
+    }
+    let bounds = longer
+        .grapheme_indices(true)
+        .map(|(idx, _)| idx)
+        .chain([longer.len()])
+        .collect::<Vec<usize>>();
+    bounds
+        .windows(len + 1)
+        .map(|window| ratio(&shorter, &longer[window[0]..window[len]]))
+        .fold(0.0, f64::max)
+}
+
+#[inline]
+fn sorted<'a>(words: &[&'a str]) -> Vec<&'a str> {
+    let mut words = words.to_vec();
+    words.sort_unstable();
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATIO: SimFunc = strsim::normalized_levenshtein;
+
+    #[test]
+    fn ignore_word_order() {
+        assert_eq!(
+            token_sort(
+                &["guisan", "général", "avenue"],
+                &["avenue", "général", "guisan"],
+                RATIO
+            ),
+            1.0
+        );
+    }
+
+    #[test]
+    fn ignore_extra_words() {
+        let (lhs, rhs) = (
+            ["abstellplätze", "bombachsteig", "9"],
+            ["bombachsteig", "9"],
+        );
+        assert_eq!(token_set(&lhs, &rhs, RATIO), 1.0);
+        assert_eq!(partial_ratio(&lhs, &rhs, RATIO), 1.0);
+        assert!(token_set(&["rue", "du", "clos"], &["avenue", "des", "alpes"], RATIO) < 0.5);
+    }
+
+    #[test]
+    fn partial_ratio_of_empty_text() {
+        assert_eq!(partial_ratio(&[], &[], RATIO), 1.0);
+        assert_eq!(partial_ratio(&[], &["foo"], RATIO), 0.0);
+    }
+}
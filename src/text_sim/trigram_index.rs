@@ -0,0 +1,349 @@
+//! Inverted index of character trigrams generating a shortlist of candidates without a full scan.
+//!
+//! The recall is guaranteed by the q-gram lemma: the texts of `m` chars at most (padded by two
+//! chars on each side) within `k` Levenshtein edits share at least `m + 2 - 3k` trigrams,
+//! so every candidate with the normalised Levenshtein similarity not lower than `recall`
+//! is in the shortlist
+use super::persist::{capacity, read_str, read_u32, write_str, write_u32};
+use crate::{
+    candidate::{Sens, Text},
+    mass::Error,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, prelude::*, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+const MAGIC: &[u8; 8] = b"MASSTRI2";
+const PADDING: char = '\0';
+const DEFAULT_RECALL: f64 = 0.7;
+
+type Trigram = [char; 3];
+
+#[derive(Debug, Clone)]
+pub struct TrigramIndex {
+    /// The lowest normalised Levenshtein similarity of the candidates guaranteed to be
+    /// in the shortlist, the lower it is the longer the shortlist is.
+    /// Below 2/3 the lemma does not prune anything, so only the length filter is applied
+    pub recall: Sens,
+    source: Option<PathBuf>,
+    texts: Vec<String>,
+    lengths: Vec<u32>,
+    postings: HashMap<Trigram, Vec<(u32, u32)>>,
+}
+
+impl TrigramIndex {
+    /// The candidates are cleaned by `Text::new`, the recall is guaranteed for the cleaned texts
+    pub fn new(candidates: Vec<String>) -> Self {
+        let mut index = Self {
+            recall: Sens(DEFAULT_RECALL),
+            source: None,
+            lengths: Vec::with_capacity(candidates.len()),
+            texts: Vec::new(),
+            postings: HashMap::new(),
+        };
+        for (id, candidate) in candidates.iter().enumerate() {
+            let cleaned = Text::new(candidate.to_owned()).cleaned;
+            index.lengths.push(cleaned.chars().count() as u32);
+            for (trigram, count) in trigrams(&cleaned) {
+                index
+                    .postings
+                    .entry(trigram)
+                    .or_default()
+                    .push((id as u32, count));
+            }
+        }
+        index.texts = candidates;
+        index
+    }
+
+    /// Builds the index from a file with a candidate on each line,
+    /// `text_sim::fast_cmp_with_file` uses the index instead of reading the same `file`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file could not be read
+    pub fn from_file(file: &Path) -> Result<Self, Error> {
+        let mut index = Self::new(
+            BufReader::new(File::open(file)?)
+                .lines()
+                .collect::<io::Result<Vec<String>>>()?,
+        );
+        index.source = Some(file.to_path_buf());
+        Ok(index)
+    }
+
+    pub fn with_recall(self, recall: Sens) -> Self {
+        Self { recall, ..self }
+    }
+
+    /// Sets the file the candidates were read from, e.g. if it was moved after `save`
+    pub fn with_source(self, source: &Path) -> Self {
+        Self {
+            source: Some(source.to_path_buf()),
+            ..self
+        }
+    }
+
+    /// The file the index was built from
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.texts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty()
+    }
+
+    /// The candidates sharing enough trigrams with `text` in their original order
+    pub fn shortlist(&self, text: &str) -> Vec<&str> {
+        self.shortlist_entries(&Text::new(text.to_string()).cleaned, self.recall)
+            .map(|(_, candidate)| candidate)
+            .collect()
+    }
+
+    /// The shortlist of the already cleaned `target` by `recall` instead of the one of the index,
+    /// with the position of each candidate in the indexed list
+    pub(super) fn shortlist_entries(
+        &self,
+        target: &str,
+        recall: Sens,
+    ) -> impl Iterator<Item = (usize, &str)> {
+        self.shortlist_ids(target, recall.0)
+            .into_iter()
+            .map(|id| (id, self.texts[id].as_str()))
+    }
+
+    /// Every candidate with its position in the indexed list
+    pub(super) fn entries(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.texts.iter().map(String::as_str).enumerate()
+    }
+
+    fn shortlist_ids(&self, target: &str, recall: f64) -> Vec<usize> {
+        let target_len = target.chars().count();
+        let mut common = HashMap::<u32, u32>::new();
+        for (trigram, target_count) in trigrams(target) {
+            for (id, count) in self.postings.get(&trigram).into_iter().flatten() {
+                *common.entry(*id).or_default() += target_count.min(*count);
+            }
+        }
+        let is_similar = |id: usize, common: u32| {
+            let len = self.lengths[id] as usize;
+            let max_len = target_len.max(len);
+            let max_edits = ((1.0 - recall) * max_len as f64 + 1e-9).floor() as usize;
+            target_len.abs_diff(len) <= max_edits && common as usize + 3 * max_edits >= max_len + 2
+        };
+        let mut ids = if 3.0 * recall < 2.0 {
+            // The candidates sharing no trigram could be similar enough as well
+            (0..self.texts.len())
+                .filter(|id| is_similar(*id, common.get(&(*id as u32)).copied().unwrap_or(0)))
+                .collect::<Vec<usize>>()
+        } else {
+            common
+                .into_iter()
+                .map(|(id, common)| (id as usize, common))
+                .filter(|(id, common)| is_similar(*id, *common))
+                .map(|(id, _)| id)
+                .collect::<Vec<usize>>()
+        };
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Persists the index with its source, so it is not built again on each start
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file could not be written
+    pub fn save(&self, file: &Path) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(file)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.recall.0.to_le_bytes())?;
+        // An empty path stands for no source
+        write_str(
+            &mut writer,
+            &self
+                .source
+                .as_deref()
+                .map(|source| source.to_string_lossy())
+                .unwrap_or_default(),
+        )?;
+        write_u32(&mut writer, self.texts.len() as u32)?;
+        for (text, len) in self.texts.iter().zip(&self.lengths) {
+            write_str(&mut writer, text)?;
+            write_u32(&mut writer, *len)?;
+        }
+        write_u32(&mut writer, self.postings.len() as u32)?;
+        for (trigram, postings) in &self.postings {
+            for ch in trigram {
+                write_u32(&mut writer, *ch as u32)?;
+            }
+            write_u32(&mut writer, postings.len() as u32)?;
+            for (id, count) in postings {
+                write_u32(&mut writer, *id)?;
+                write_u32(&mut writer, *count)?;
+            }
+        }
+        Ok(writer.flush()?)
+    }
+
+    /// Loads the index persisted by `TrigramIndex::save`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the file could not be read
+    /// and `Error::InvalidConfig` if it is not a persisted index
+    pub fn load(file: &Path) -> Result<Self, Error> {
+        let invalid = || Error::InvalidConfig(format!("{} is not a trigram index", file.display()));
+        let mut reader = BufReader::new(File::open(file)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid());
+        }
+        let mut recall = [0; 8];
+        reader.read_exact(&mut recall)?;
+        let recall = Sens::new(f64::from_le_bytes(recall)).map_err(|_| invalid())?;
+        let source = read_str(&mut reader)?.ok_or_else(invalid)?;
+        let source = (!source.is_empty()).then(|| PathBuf::from(source));
+        let num_of_texts = read_u32(&mut reader)? as usize;
+        let mut texts = Vec::with_capacity(capacity(num_of_texts));
+        let mut lengths = Vec::with_capacity(capacity(num_of_texts));
+        for _ in 0..num_of_texts {
+            texts.push(read_str(&mut reader)?.ok_or_else(invalid)?);
+            lengths.push(read_u32(&mut reader)?);
+        }
+        let num_of_trigrams = read_u32(&mut reader)? as usize;
+        let mut postings = HashMap::with_capacity(capacity(num_of_trigrams));
+        for _ in 0..num_of_trigrams {
+            let mut trigram = [PADDING; 3];
+            for ch in trigram.iter_mut() {
+                *ch = char::from_u32(read_u32(&mut reader)?).ok_or_else(invalid)?;
+            }
+            let len = read_u32(&mut reader)? as usize;
+            let mut ids = Vec::with_capacity(capacity(len));
+            for _ in 0..len {
+                let id = read_u32(&mut reader)?;
+                if id as usize >= num_of_texts {
+                    return Err(invalid());
+                }
+                ids.push((id, read_u32(&mut reader)?));
+            }
+            postings.insert(trigram, ids);
+        }
+        Ok(Self {
+            recall,
+            source,
+            texts,
+            lengths,
+            postings,
+        })
+    }
+}
+
+/// The distinct trigrams of `text` padded by two chars on each side with their counts
+#[inline]
+fn trigrams(text: &str) -> Vec<(Trigram, u32)> {
+    let chars = [PADDING, PADDING]
+        .into_iter()
+        .chain(text.chars())
+        .chain([PADDING, PADDING])
+        .collect::<Vec<char>>();
+    let mut trigrams = chars
+        .windows(3)
+        .map(|window| [window[0], window[1], window[2]])
+        .collect::<Vec<Trigram>>();
+    trigrams.sort_unstable();
+    let mut counts: Vec<(Trigram, u32)> = Vec::with_capacity(trigrams.len());
+    for trigram in trigrams {
+        match counts.last_mut() {
+            Some((last, count)) if *last == trigram => *count += 1,
+            _ => counts.push((trigram, 1)),
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const DATA_FILE: &str = "./test_data/streets_data/street_names.txt";
+
+    #[test]
+    fn shortlist_keeps_similar() {
+        let index = TrigramIndex::from_file(Path::new(DATA_FILE)).unwrap();
+        let shortlist = index.shortlist("qu du seujet");
+        assert!(shortlist.contains(&"quai du seujet"));
+        assert!(shortlist.len() < index.len());
+    }
+
+    #[test]
+    fn recall_guarantee() {
+        let names = BufReader::new(File::open(DATA_FILE).unwrap())
+            .lines()
+            .collect::<io::Result<Vec<String>>>()
+            .unwrap();
+        for recall in [0.6, 0.7, 0.8, 0.9] {
+            let index = TrigramIndex::new(names.clone()).with_recall(Sens(recall));
+            for target in ["qu du seujet", "bernstrase", "ch de saint-cierges", "x"] {
+                let cleaned = Text::new(target.to_string()).cleaned;
+                let shortlist = index.shortlist(target);
+                for name in &names {
+                    if strsim::normalized_levenshtein(&cleaned, &Text::new(name.to_owned()).cleaned)
+                        >= recall
+                    {
+                        assert!(shortlist.contains(&name.as_str()), "{} {}", target, name);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let index = TrigramIndex::from_file(Path::new(DATA_FILE))
+            .unwrap()
+            .with_recall(Sens(0.8));
+        let file = env::temp_dir().join(format!("mass_trigrams_{}", std::process::id()));
+        index.save(&file).unwrap();
+        let loaded = TrigramIndex::load(&file).unwrap();
+        assert_eq!(loaded.recall.0, 0.8);
+        assert_eq!(loaded.source(), Some(Path::new(DATA_FILE)));
+        assert_eq!(
+            loaded.shortlist("qu du seujet"),
+            index.shortlist("qu du seujet")
+        );
+        std::fs::write(&file, "foo").unwrap();
+        assert!(TrigramIndex::load(&file).is_err());
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn load_truncated() {
+        let index = TrigramIndex::from_file(Path::new(DATA_FILE)).unwrap();
+        let file = env::temp_dir().join(format!("mass_truncated_trigrams_{}", std::process::id()));
+        index.save(&file).unwrap();
+        let bytes = std::fs::read(&file).unwrap();
+        std::fs::write(&file, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(matches!(TrigramIndex::load(&file), Err(Error::Io(_))));
+        // The counts are not trusted, so the huge ones do not allocate anything up front
+        let header = [MAGIC.as_slice(), &0.7_f64.to_le_bytes()].concat();
+        // A huge length of the source, then an empty source and a huge number of texts
+        for counts in [vec![u32::MAX], vec![0, u32::MAX]] {
+            let mut bytes = header.clone();
+            for count in counts {
+                bytes.extend_from_slice(&count.to_le_bytes());
+            }
+            std::fs::write(&file, &bytes).unwrap();
+            assert!(matches!(TrigramIndex::load(&file), Err(Error::Io(_))));
+        }
+        std::fs::remove_file(file).unwrap();
+    }
+}